@@ -1,4 +1,5 @@
 use crate::timeseries::*;
+use crate::follow::Progress;
 use crate::ui;
 
 use ratatui::layout::Rect;
@@ -12,7 +13,8 @@ pub struct Layout {
     pub stream_width: u64,
     pub sidebar_width_percent: u16,
     pub signame_width_percent: u16,
-    pub timedelta_width: u64,
+    pub timedelta_width: u64,  // columns per tick. 1 when zoomed out further
+    pub ticks_per_column: u64, // when zoomed out, changes in a column are summarised
 
     pub current_width: u16,
     pub current_height: u16,
@@ -26,6 +28,50 @@ impl Layout {
         // -1 for the first border line
         self.drawable_lines = (self.current_height-1) as usize / 2;
     }
+
+    pub fn is_zoomed_out(&self) -> bool {
+        self.timedelta_width < 2
+    }
+
+    // the number of ticks shown in the waveform pane
+    pub fn visible_ticks(&self) -> u64 {
+        self.stream_width * self.ticks_per_column / self.timedelta_width
+    }
+
+    fn zoom_in(&mut self) {
+        if 1 < self.ticks_per_column {
+            // 1, 2, 5, 10, 20, 50, ...
+            let magnitude = 10_u64.pow(self.ticks_per_column.ilog10());
+            self.ticks_per_column = match self.ticks_per_column / magnitude {
+                1 => { magnitude / 10 * 5 }
+                2 => { magnitude }
+                _ => { magnitude * 2 }
+            };
+        } else {
+            self.timedelta_width += 1;
+        }
+    }
+
+    // stops when the whole trace fits in the pane
+    fn zoom_out(&mut self, t_last: u64) {
+        if 1 < self.timedelta_width {
+            self.timedelta_width -= 1;
+        } else if self.visible_ticks() <= t_last {
+            let magnitude = 10_u64.pow(self.ticks_per_column.ilog10());
+            self.ticks_per_column = match self.ticks_per_column / magnitude {
+                1 => { magnitude * 2 }
+                2 => { magnitude * 5 }
+                _ => { magnitude * 10 }
+            };
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum TreeAction {
+    Toggle,   // check/uncheck a value, open/close a scope
+    Expand,   // show bits of a bus, open a scope
+    Collapse, // hide bits of a bus, close a scope
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
@@ -45,6 +91,16 @@ pub struct TuiWave {
     pub layout: Layout,
     pub should_quit: bool,
     pub window_change_mode: bool,
+    pub auto_scroll: bool, // keep showing the latest time (follow mode)
+    pub radix: Vec<Radix>, // for each value stream
+    pub widths: Vec<usize>, // declared width of each value stream
+    pub sort_order: SortOrder,
+    pub command: Option<String>, // goto prompt, while it is being typed
+    pub loading: Option<Progress>, // while the file is loaded in the background
+    pub cancel_loading: bool,
+    pub show_info: bool, // file information panel
+    pub file_name: String,
+    pub file_size: Option<u64>,
 
     pub focus: Focus,
     pub focus_signal: usize,
@@ -60,10 +116,14 @@ impl TuiWave {
             sidebar_width_percent: 20,
             signame_width_percent: 15,
             timedelta_width: 4,
+            ticks_per_column: 1,
             current_width: 0,
             current_height: 0,
         };
         let cache = UICache::new(&ts);
+        let radix = vec![Radix::Hex; ts.values.len()];
+        let mut widths = vec![0; ts.values.len()];
+        Self::declared_widths(&ts.scope, &mut widths);
 
         Self{
             ts,
@@ -76,6 +136,16 @@ impl TuiWave {
             layout,
             should_quit: false,
             window_change_mode: false,
+            auto_scroll: false,
+            radix,
+            widths,
+            sort_order: SortOrder::Declaration,
+            command: None,
+            loading: None,
+            cancel_loading: false,
+            show_info: false,
+            file_name: String::new(),
+            file_size: None,
             focus: Focus::Signal,
             focus_signal: 0,
             focus_tree: 0,
@@ -86,44 +156,111 @@ impl TuiWave {
     fn setup_drawable_time_range(&mut self) {
         let main_pane = self.layout.current_width * (100 - self.layout.sidebar_width_percent) / 100;
         self.layout.stream_width = (main_pane * (100 - self.layout.signame_width_percent) / 100) as u64;
-        let time_range = self.layout.stream_width / self.layout.timedelta_width;
+        let time_range = self.layout.visible_ticks();
         self.t_to = (self.t_from + time_range).min(self.t_last+1);
         self.t_range = self.t_to - self.t_from;
     }
 
+    // called when new value changes are appended (follow mode)
+    pub fn update_time_range(&mut self, t_last: u64) {
+        self.t_last = self.t_last.max(t_last);
+        self.setup_drawable_time_range();
+        if self.auto_scroll {
+            let time_range = self.layout.visible_ticks();
+            self.t_from = (self.t_last + 1).saturating_sub(time_range);
+            self.setup_drawable_time_range();
+        }
+        self.render_waveform();
+    }
+
+    // the loader moved the times onto a finer tick (`$timescale` in the middle of the file)
+    pub fn scale_time(&mut self, factor: u64) {
+        self.t_from  = self.t_from .saturating_mul(factor);
+        self.t_to    = self.t_to   .saturating_mul(factor);
+        self.t_last  = self.t_last .saturating_mul(factor);
+        self.t_range = self.t_range.saturating_mul(factor);
+        self.layout.ticks_per_column = self.layout.ticks_per_column.saturating_mul(factor);
+    }
+
     pub fn setup_with_terminal_size(&mut self, termsize: Rect) {
         self.layout.resize(termsize.width, termsize.height);
         self.setup_drawable_time_range();
         self.render_waveform();
     }
 
+    // `12.5 ns`, `3us`, or ticks without a unit
+    fn goto(&mut self, text: &str) -> bool {
+        let Some(t) = self.ts.time_scale.parse(text) else {
+            return false;
+        };
+        self.auto_scroll = false;
+        self.t_from = t.min(self.t_last);
+        self.setup_drawable_time_range();
+        self.render_waveform();
+        true
+    }
+
+    fn command_key_press(&mut self, key: KeyCode) {
+        let Some(command) = &mut self.command else {
+            return;
+        };
+        match key {
+            KeyCode::Char(c) => { command.push(c); }
+            KeyCode::Backspace => { command.pop(); }
+            KeyCode::Esc => { self.command = None; }
+            KeyCode::Enter => {
+                let text = command.clone();
+                if self.goto(&text) {
+                    self.command = None;
+                }
+            }
+            _ => {}
+        }
+    }
+
     pub fn key_press(&mut self, key: KeyCode, modifiers: KeyModifiers, _state: KeyEventState) {
-        if key == KeyCode::Char('q') {
+        if self.command.is_some() {
+            self.command_key_press(key);
+        } else if self.show_info && (key == KeyCode::Esc || key == KeyCode::Char('i')) {
+            self.show_info = false;
+        } else if key == KeyCode::Char('q') {
             self.should_quit = true;
+        } else if key == KeyCode::Esc && self.loading.is_some() {
+            self.cancel_loading = true;
         } else if key == KeyCode::Char('l') || key == KeyCode::Right {
             if self.window_change_mode {
                 self.focus = Focus::Signal;
                 self.window_change_mode = false;
+            } else if self.focus == Focus::Tree {
+                self.flip_scope_tree(TreeAction::Expand);
+                self.cache.update_selection(&self.ts);
+                self.render_waveform();
             } else if self.focus == Focus::Signal {
-                self.t_from = self.t_from.saturating_add(1).min(self.t_last);
-                self.t_to   = self.t_to  .saturating_add(1).min(self.t_last + self.t_range);
+                let dt = self.layout.ticks_per_column; // a column
+                self.t_from = self.t_from.saturating_add(dt).min(self.t_last);
+                self.t_to   = self.t_to  .saturating_add(dt).min(self.t_last + self.t_range);
                 self.render_waveform();
             }
         } else if key == KeyCode::Char('h') || key == KeyCode::Left {
             if self.window_change_mode {
                 self.focus = Focus::Tree;
                 self.window_change_mode = false;
-            } else if self.focus == Focus::Signal {
-                if self.t_from != 0 {
-                    self.t_from = self.t_from.saturating_sub(1);
-                    self.t_to   = self.t_to  .saturating_sub(1);
-                    self.render_waveform();
-                }
+            } else if self.focus == Focus::Tree {
+                self.flip_scope_tree(TreeAction::Collapse);
+                self.cache.update_selection(&self.ts);
+                self.render_waveform();
+            } else if self.focus == Focus::Signal && self.t_from != 0 {
+                self.auto_scroll = false;
+                let dt = self.layout.ticks_per_column; // a column
+                self.t_from = self.t_from.saturating_sub(dt);
+                self.t_to   = self.t_to  .saturating_sub(dt);
+                self.render_waveform();
             }
         } else if key == KeyCode::Char('j') || key == KeyCode::Down {
             if self.focus == Focus::Signal {
+                // aliased signals share a stream, so there can be more lines than values
                 self.focus_signal = (self.focus_signal + 1)
-                    .min(self.ts.values.len().saturating_sub(1));
+                    .min(self.cache.selected_values.len().saturating_sub(1));
 
                 if (self.layout.drawable_lines + self.line_from).saturating_sub(1) < self.focus_signal {
                     self.line_from = self.focus_signal - self.layout.drawable_lines + 1;
@@ -143,14 +280,15 @@ impl TuiWave {
                 self.focus_tree = self.focus_tree.saturating_sub(1)
             }
         } else if key == KeyCode::Char('-') {
-            self.layout.timedelta_width = self.layout.timedelta_width.saturating_sub(1).max(2);
+            self.layout.zoom_out(self.t_last);
             self.setup_drawable_time_range();
             self.render_waveform();
         } else if key == KeyCode::Char('+') {
-            self.layout.timedelta_width = self.layout.timedelta_width.saturating_add(1).max(2);
+            self.layout.zoom_in();
             self.setup_drawable_time_range();
             self.render_waveform();
         } else if key == KeyCode::Char('0') {
+            self.auto_scroll = false;
             let dt = self.t_to.saturating_sub(self.t_from);
             self.t_to   = dt;
             self.t_from = 0;
@@ -160,14 +298,42 @@ impl TuiWave {
             self.t_to   = self.t_last;
             self.t_from = self.t_last.saturating_sub(dt);
             self.render_waveform();
+        } else if key == KeyCode::Char('g') {
+            self.command = Some(String::new());
+        } else if key == KeyCode::Char('i') {
+            self.show_info = true;
+        } else if key == KeyCode::Char('f') {
+            self.auto_scroll = !self.auto_scroll;
+            if self.auto_scroll {
+                self.update_time_range(self.t_last);
+            }
+        } else if key == KeyCode::Char('r') {
+            if self.focus == Focus::Signal {
+                if let Some((_, idx)) = self.cache.selected_values.get(self.focus_signal) {
+                    self.radix[*idx] = match self.radix[*idx] {
+                        Radix::Hex => { Radix::Bin }
+                        Radix::Bin => { Radix::Dec }
+                        Radix::Dec => { Radix::Hex }
+                    };
+                    self.render_waveform();
+                }
+            }
+        } else if key == KeyCode::Char('s') {
+            self.sort_order = match self.sort_order {
+                SortOrder::Declaration  => { SortOrder::Alphabetical }
+                SortOrder::Alphabetical => { SortOrder::Width }
+                SortOrder::Width        => { SortOrder::Type }
+                SortOrder::Type         => { SortOrder::Declaration }
+            };
+            self.ts.scope.sort_items(self.sort_order, &self.ts.values);
+            self.cache.update_selection(&self.ts);
+            self.render_waveform();
         } else if modifiers == KeyModifiers::CONTROL && key == KeyCode::Char('w') {
             self.window_change_mode = true;
-        } else if key == KeyCode::Enter {
-            if self.focus == Focus::Tree {
-                self.flip_scope_tree();
-                self.cache.update_selection(&self.ts);
-                self.render_waveform();
-            }
+        } else if key == KeyCode::Enter && self.focus == Focus::Tree {
+            self.flip_scope_tree(TreeAction::Toggle);
+            self.cache.update_selection(&self.ts);
+            self.render_waveform();
         }
     }
 
@@ -185,9 +351,13 @@ impl TuiWave {
         }
     }
 
-    fn flip_scope_tree_impl(node: &mut Scope, i: &mut usize, flipped: usize) -> bool {
+    fn flip_scope_tree_impl(node: &mut Scope, i: &mut usize, flipped: usize, action: TreeAction) -> bool {
         if *i == flipped {
-            node.open = !node.open;
+            node.open = match action {
+                TreeAction::Toggle   => { !node.open }
+                TreeAction::Expand   => { true }
+                TreeAction::Collapse => { false }
+            };
             return true;
         }
         *i += 1;
@@ -199,32 +369,65 @@ impl TuiWave {
         for item in node.items.iter_mut() {
             if let ScopeItem::Value(v) = item {
                 if *i == flipped {
-                    v.render = !v.render;
+                    match action {
+                        TreeAction::Toggle   => { v.render = !v.render; }
+                        TreeAction::Expand   => { v.open = true; }
+                        TreeAction::Collapse => { v.open = false; }
+                    }
                     return true;
                 }
                 *i += 1;
+
+                if v.open {
+                    for b in v.bits.iter_mut() {
+                        if *i == flipped {
+                            if action == TreeAction::Toggle {
+                                b.render = !b.render;
+                            }
+                            return true;
+                        }
+                        *i += 1;
+                    }
+                }
             }
         }
         for item in node.items.iter_mut() {
             if let ScopeItem::Scope(s) = item {
-                let done = Self::flip_scope_tree_impl(s, i, flipped);
+                let done = Self::flip_scope_tree_impl(s, i, flipped, action);
                 if done {
                     return true;
                 }
             }
         }
-        return false;
+        false
     }
-    fn flip_scope_tree(&mut self) {
+    fn flip_scope_tree(&mut self, action: TreeAction) {
         let mut idx = 0;
-        let done =  Self::flip_scope_tree_impl(&mut self.ts.scope, &mut idx, self.focus_tree);
+        let done =  Self::flip_scope_tree_impl(&mut self.ts.scope, &mut idx, self.focus_tree, action);
         assert!(done);
     }
 
+    fn declared_widths(s: &Scope, widths: &mut [usize]) {
+        for item in s.items.iter() {
+            match item {
+                ScopeItem::Value(v) => {
+                    for v in std::iter::once(v).chain(v.bits.iter()) {
+                        widths[v.index] = v.width;
+                    }
+                }
+                ScopeItem::Scope(s) => { Self::declared_widths(s, widths); }
+            }
+        }
+    }
+
     fn render_waveform(&mut self) {
         let values = &self.cache.selected_values;
+        if values.is_empty() {
+            self.cache.signal_timelines.clear();
+            return;
+        }
         let line_to = (self.line_from + self.layout.drawable_lines - 1).min(values.len()-1);
-        self.cache.signal_timelines = ui::format_values(&self, &values[self.line_from..=line_to]);
+        self.cache.signal_timelines = ui::format_values(self, &values[self.line_from..=line_to]);
     }
 }
 
@@ -256,7 +459,12 @@ impl UICache {
                 }
                 let mut path_to_item = path.to_string();
                 path_to_item += ".";
-                vs.push(((path_to_item, v.name.clone()), v.index));
+                if v.render {
+                    vs.push(((path_to_item.clone(), v.label()), v.index));
+                }
+                for b in v.bits.iter().filter(|b| b.should_be_rendered()) {
+                    vs.push(((path_to_item.clone(), b.label()), b.index));
+                }
             }
         }
         for item in s.items.iter() {
@@ -275,7 +483,7 @@ impl UICache {
 
     fn list_values(root: &Scope) -> Vec<((String, String), usize)> {
         let mut vs = Vec::new();
-        Self::list_values_impl(&root, &root.name[0..1], &mut vs);
+        Self::list_values_impl(root, &root.name[0..1], &mut vs);
         vs
     }
 
@@ -296,9 +504,22 @@ impl UICache {
         for item in s.items.iter() {
             let is_last = (n_scopes == 0) && (c_values + 1) == n_values;
             if let ScopeItem::Value(v) = item {
-                let cbox = if v.should_be_rendered() { "☑"  } else { "☐"  };
+                let cbox = if v.render { "☑"  } else { "☐"  };
                 let branch = if is_last { "└" } else { "├" };
-                lines.push(format!("{}{}╴{} {}", indent, branch, cbox, v.name));
+                if v.bits.is_empty() {
+                    lines.push(format!("{}{}╴{} {}", indent, branch, cbox, v.label()));
+                } else {
+                    let open_icon = if v.open { "▼" } else { "▶" };
+                    lines.push(format!("{}{}╴{} {} {}", indent, branch, open_icon, cbox, v.label()));
+                }
+                if v.open {
+                    let bit_indent = indent.clone() + (if is_last { "  " } else { "│ " });
+                    for (j, b) in v.bits.iter().enumerate() {
+                        let cbox = if b.render { "☑"  } else { "☐"  };
+                        let branch = if j + 1 == v.bits.len() { "└" } else { "├" };
+                        lines.push(format!("{}{}╴{} {}", bit_indent, branch, cbox, b.label()));
+                    }
+                }
                 c_values += 1;
             }
         }
@@ -310,16 +531,33 @@ impl UICache {
                 let branch = if is_last { "└" } else { "├" };
                 let next_indent = indent.clone() + (if is_last { "  " } else { "│ " });
                 let open_icon = if subscope.open { "▼" } else { "▶" };
-                lines.push(format!("{}{}╴{} {}", indent, branch, open_icon, subscope.name));
+                lines.push(format!("{}{}╴{} {} {}", indent, branch, open_icon, Self::scope_icon(subscope.kind), subscope.name));
 
                 Self::draw_scope_tree_impl(subscope, lines, next_indent);
                 c_scopes += 1;
             }
         }
     }
+    fn scope_icon(kind: ScopeKind) -> &'static str {
+        match kind {
+            ScopeKind::Module    => { "■" }
+            ScopeKind::Interface => { "◈" }
+            ScopeKind::Program   => { "◉" }
+            ScopeKind::Package   => { "▣" }
+            ScopeKind::Class     => { "◎" }
+            ScopeKind::Task      => { "○" }
+            ScopeKind::Function  => { "●" }
+            ScopeKind::Begin     => { "◇" }
+            ScopeKind::Generate  => { "◊" }
+            ScopeKind::Fork      => { "◆" }
+            ScopeKind::Struct    => { "△" }
+            ScopeKind::Union     => { "▲" }
+            ScopeKind::Other     => { "·" }
+        }
+    }
     fn draw_scope_tree(root: &Scope) -> Vec<String> {
         let mut tree = vec![root.name.clone()];
-        Self::draw_scope_tree_impl(&root, &mut tree, "".to_string());
+        Self::draw_scope_tree_impl(root, &mut tree, "".to_string());
         tree
     }
 }
@@ -0,0 +1,245 @@
+use crate::timeseries::*;
+
+use std::collections::*;
+use std::sync::Arc;
+
+// `data[3]` -> ("data", 3). VCD may also write it as `data [3]`
+fn split_bit_index(v: &ScopeValue) -> Option<(String, i32)> {
+    if v.width != 1 {
+        return None;
+    }
+    match v.range {
+        Some(BitRange::Bit(i)) => { Some((v.name.clone(), i)) }
+        Some(_)                => { None }
+        None => {
+            let (base, idx) = v.name.strip_suffix(']')?.rsplit_once('[')?;
+            Some((base.to_string(), idx.trim().parse().ok()?))
+        }
+    }
+}
+
+fn group_scope(scope: &mut Scope, values: &mut Vec<ValueChangeStream>, buses: &mut Vec<Bus>) {
+
+    // base name -> [(bit index, position in items)], in the order of appearance
+    let mut families: Vec<(String, Vec<(i32, usize)>)> = Vec::new();
+    for (pos, item) in scope.items.iter().enumerate() {
+        let ScopeItem::Value(v) = item else {
+            continue;
+        };
+        if !matches!(values[v.index], ValueChangeStream::Bits(_)) {
+            continue;
+        }
+        if let Some((base, i)) = split_bit_index(v) {
+            match families.iter_mut().find(|(name, _)| *name == base) {
+                Some((_, members)) => { members.push((i, pos)); }
+                None               => { families.push((base, vec![(i, pos)])); }
+            }
+        }
+    }
+
+    let mut grouped: HashMap<usize, ScopeValue> = HashMap::new(); // first member -> bus
+    let mut removed = HashSet::new();
+    for (base, mut members) in families {
+        members.sort();
+        if members.len() < 2 || members.windows(2).any(|w| w[0].0 == w[1].0) {
+            continue; // not a bus, or the same bit appears twice
+        }
+        let lo = members.first().unwrap().0;
+        let hi = members.last().unwrap().0;
+
+        let mut bits = vec![None; (hi - lo + 1) as usize];
+        let mut children = Vec::new();
+        for (i, pos) in members.iter().rev() {
+            let ScopeItem::Value(v) = &scope.items[*pos] else {
+                unreachable!();
+            };
+            bits[(i - lo) as usize] = Some(v.index);
+
+            let mut child = v.clone();
+            child.render = false; // the bus is shown instead
+            children.push(child);
+            removed.insert(*pos);
+        }
+
+        values.push(ValueChangeStream::Bits(ValueChangeStreamImpl::new()));
+        let index = values.len() - 1;
+        buses.push(Bus{ index, bits });
+
+        let first = members.iter().map(|(_, pos)| *pos).min().unwrap();
+        let mut bus = ScopeValue::new(&base, index, (hi - lo + 1) as usize);
+        bus.range = Some(BitRange::Range(hi, lo));
+        bus.bits  = children;
+        if let ScopeItem::Value(v) = &scope.items[first] {
+            bus.order = v.order;
+        }
+        grouped.insert(first, bus);
+    }
+
+    if !removed.is_empty() {
+        let items = std::mem::take(&mut scope.items);
+        for (pos, item) in items.into_iter().enumerate() {
+            if let Some(bus) = grouped.remove(&pos) {
+                scope.items.push(ScopeItem::Value(bus));
+            } else if !removed.contains(&pos) {
+                scope.items.push(item);
+            }
+        }
+    }
+
+    for item in scope.items.iter_mut() {
+        if let ScopeItem::Scope(s) = item {
+            group_scope(s, values, buses);
+        }
+    }
+}
+
+// gate-level netlists and some simulators dump a bus as scalars, `data[0]`, `data[1]`, ...
+// this combines them into a vector and moves the scalars under it in the tree.
+// call `update_buses` after value changes are loaded.
+pub fn group_bit_blasted(ts: &mut TimeSeries) {
+    group_scope(&mut ts.scope, Arc::make_mut(&mut ts.values), &mut ts.buses);
+}
+
+// computes value changes of buses after their last change, so that it can be called
+// every time value changes are appended (follow mode)
+pub fn update_buses(ts: &mut TimeSeries) {
+    for bus in ts.buses.iter() {
+        let ValueChangeStream::Bits(xs) = &ts.values[bus.index] else {
+            continue;
+        };
+        let from = xs.last().map(|c| c.time);
+
+        let mut current = vec![vcd::Value::X; bus.bits.len()];
+        let mut changes = Vec::new(); // (time, position, value)
+        for (pos, bit) in bus.bits.iter().enumerate() {
+            let Some(ValueChangeStream::Bits(ys)) = bit.map(|i| &ts.values[i]) else {
+                continue;
+            };
+            let start = from.map(|t| ys.first_change_from(t)).unwrap_or(0);
+            if 0 < start {
+                current[pos] = ys.value(start - 1).lsb();
+            }
+            changes.extend(ys.iter_from(start).map(|c| (c.time, pos, c.new_value.lsb())));
+        }
+        changes.sort_by_key(|(t, _, _)| *t);
+
+        let mut combined = Vec::new();
+        for (i, (t, pos, v)) in changes.iter().enumerate() {
+            current[*pos] = *v;
+            if changes.get(i + 1).is_none_or(|next| next.0 != *t) {
+                combined.push((*t, Bits::from_lsb_first(current.iter().copied())));
+            }
+        }
+
+        let ValueChangeStream::Bits(xs) = &mut Arc::make_mut(&mut ts.values)[bus.index] else {
+            continue;
+        };
+        for (t, v) in combined {
+            // a change at the same time replaces the last one
+            let last = xs.last();
+            let same_time = last.as_ref().is_some_and(|last| last.time == t);
+            if same_time || last.is_none_or(|last| last.new_value != v) {
+                xs.push_change(t, v);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const VCD: &str = "$timescale 1ns $end
+$scope module top $end
+$var wire 1 ! d[0] $end
+$var wire 1 \" clk $end
+$var wire 1 # d[1] $end
+$var wire 1 $ d [3] $end
+$var wire 1 % one[0] $end
+$var wire 1 & dup[0] $end
+$var wire 1 ' dup[0] $end
+$upscope $end
+$enddefinitions $end
+#0
+0!
+0\"
+1#
+1$
+0%
+0&
+0'
+#10
+1!
+1\"
+#20
+0#
+#30
+1!
+0$
+";
+
+    fn load(vcd: &str) -> TimeSeries {
+        crate::load_vcd::load_vcd(vcd.as_bytes(), &LoadOptions::default()).unwrap()
+    }
+
+    fn top(ts: &TimeSeries) -> &Scope {
+        let ScopeItem::Scope(top) = &ts.scope.items[0] else { panic!("no top scope") };
+        top
+    }
+
+    fn changes(ts: &TimeSeries, index: usize) -> Vec<(u64, String)> {
+        let ValueChangeStream::Bits(xs) = &ts.values[index] else { panic!("not bits") };
+        xs.iter_from(0).map(|c| (c.time, c.new_value.to_radix_string(Radix::Bin))).collect()
+    }
+
+    #[test]
+    fn groups_bits_into_a_bus() {
+        let ts = load(VCD);
+        let labels: Vec<String> = top(&ts).items.iter().map(|item| match item {
+            ScopeItem::Value(v) => { v.label() }
+            ScopeItem::Scope(s) => { s.name.clone() }
+        }).collect();
+        // in place of the first bit. a single bit or the same bit twice is not a bus
+        assert_eq!(labels, vec!["d[3:0] (4)", "clk", "one[0]", "dup[0]", "dup[0]"]);
+
+        let ScopeItem::Value(bus) = &top(&ts).items[0] else { panic!("not a value") };
+        let bits: Vec<(String, bool)> = bus.bits.iter().map(|b| (b.name.clone(), b.render)).collect();
+        assert_eq!(bits, vec![("d".to_string(), false), ("d[1]".to_string(), false), ("d[0]".to_string(), false)]);
+        assert_eq!(ts.buses.len(), 1);
+        assert_eq!(ts.buses[0].bits.iter().filter(|b| b.is_none()).count(), 1); // d[2]
+
+        // the missing bit is X
+        assert_eq!(changes(&ts, bus.index), vec![
+            (0, "1X10".to_string()), (10, "1X11".to_string()), (20, "1X01".to_string()), (30, "0X01".to_string()),
+        ]);
+    }
+
+    #[test]
+    fn updates_after_the_last_change() {
+        let full = load(VCD);
+        let (head, tail) = VCD.split_at(VCD.find("#20").unwrap());
+        let mut ts = load(head);
+        assert_eq!(changes(&ts, ts.buses[0].index), changes(&full, full.buses[0].index)[..2]);
+
+        // as in follow mode, the rest is appended to the bits, then to the bus
+        let parser = crate::load_vcd::new_body_parser(tail.as_bytes());
+        let mut t = 0;
+        let index_of = |name: &str| top(&full).items.iter().find_map(|item| match item {
+            ScopeItem::Value(v) if v.name == "d" => { v.bits.iter().find(|b| b.name == name).map(|b| b.index) }
+            _ => { None }
+        }).unwrap();
+        for cmd in parser {
+            match cmd.unwrap() {
+                vcd::Command::Timestamp(x)          => { t = x; }
+                vcd::Command::ChangeScalar(code, v) => {
+                    let name = match code.to_string().as_str() { "!" => { "d[0]" } "#" => { "d[1]" } _ => { "d" } };
+                    let ValueChangeStream::Bits(xs) = &mut ts.values_mut()[index_of(name)] else { panic!("not bits") };
+                    xs.push_change(t, Bits::from_vcd_scalar(v));
+                }
+                _ => {}
+            }
+            update_buses(&mut ts);
+        }
+        assert_eq!(changes(&ts, ts.buses[0].index), changes(&full, full.buses[0].index));
+    }
+}
@@ -0,0 +1,732 @@
+use crate::timeseries::*;
+
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+// a parsed trace is saved in a binary file next to the VCD (or in ~/.cache/tuiwave if the
+// directory is not writable), so that the next open does not parse the text again.
+// the file is memory mapped and the columns are copied out of it as they are.
+// the seek index (seek.rs) is written in the same way.
+
+const MAGIC: &[u8; 8] = b"TUIWAVE\n";
+const VERSION: u32 = 2; // bump it when the layout of anything written here changes
+
+pub trait Cached: Sized {
+    fn write(&self, w: &mut dyn Write) -> std::io::Result<()>;
+    fn read(r: &mut Reader<'_>) -> anyhow::Result<Self>;
+
+    // a `Vec` is written element by element, unless the type copies it at once
+    fn write_slice(xs: &[Self], w: &mut dyn Write) -> std::io::Result<()> {
+        xs.iter().try_for_each(|x| x.write(w))
+    }
+    fn read_vec(r: &mut Reader<'_>, n: usize) -> anyhow::Result<Vec<Self>> {
+        (0..n).map(|_| Self::read(r)).collect()
+    }
+}
+
+pub struct Reader<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    pub fn new(buf: &'a [u8]) -> Self {
+        Self{ buf, pos: 0 }
+    }
+
+    pub fn is_end(&self) -> bool {
+        self.pos == self.buf.len()
+    }
+
+    pub fn bytes(&mut self, n: usize) -> anyhow::Result<&'a [u8]> {
+        if self.buf.len() - self.pos < n {
+            return Err(anyhow::anyhow!("truncated cache"));
+        }
+        self.pos += n;
+        Ok(&self.buf[self.pos - n..self.pos])
+    }
+
+    fn array<const N: usize>(&mut self) -> anyhow::Result<[u8; N]> {
+        Ok(self.bytes(N)?.try_into().unwrap())
+    }
+}
+
+// byte arrays are copied at once, not byte by byte as `Vec<u8>`
+pub fn write_bytes(w: &mut dyn Write, bytes: &[u8]) -> std::io::Result<()> {
+    bytes.len().write(w)?;
+    w.write_all(bytes)
+}
+
+pub fn read_bytes(r: &mut Reader<'_>) -> anyhow::Result<Vec<u8>> {
+    let n = usize::read(r)?;
+    Ok(r.bytes(n)?.to_vec())
+}
+
+// numbers are little endian. a `Vec` of them is copied from the mapped file at once
+macro_rules! cached_number {
+    ($($t:ty),*) => { $(
+        impl Cached for $t {
+            fn write(&self, w: &mut dyn Write) -> std::io::Result<()> { w.write_all(&self.to_le_bytes()) }
+            fn read(r: &mut Reader<'_>) -> anyhow::Result<Self> { Ok(<$t>::from_le_bytes(r.array()?)) }
+
+            fn write_slice(xs: &[Self], w: &mut dyn Write) -> std::io::Result<()> {
+                for chunk in xs.chunks(8192) {
+                    let bytes: Vec<u8> = chunk.iter().flat_map(|x| x.to_le_bytes()).collect();
+                    w.write_all(&bytes)?;
+                }
+                Ok(())
+            }
+            fn read_vec(r: &mut Reader<'_>, n: usize) -> anyhow::Result<Vec<Self>> {
+                const SIZE: usize = std::mem::size_of::<$t>();
+                let bytes = r.bytes(n.checked_mul(SIZE).ok_or_else(|| anyhow::anyhow!("truncated cache"))?)?;
+                Ok(bytes.chunks_exact(SIZE).map(|b| <$t>::from_le_bytes(b.try_into().unwrap())).collect())
+            }
+        }
+    )* }
+}
+
+cached_number!(u8, u32, i32, u64, f64);
+
+impl Cached for usize {
+    fn write(&self, w: &mut dyn Write) -> std::io::Result<()> { (*self as u64).write(w) }
+    fn read(r: &mut Reader<'_>) -> anyhow::Result<Self> { Ok(usize::try_from(u64::read(r)?)?) }
+
+    fn write_slice(xs: &[Self], w: &mut dyn Write) -> std::io::Result<()> {
+        xs.chunks(8192).try_for_each(|chunk| u64::write_slice(&chunk.iter().map(|x| *x as u64).collect::<Vec<_>>(), w))
+    }
+    fn read_vec(r: &mut Reader<'_>, n: usize) -> anyhow::Result<Vec<Self>> {
+        Ok(u64::read_vec(r, n)?.into_iter().map(usize::try_from).collect::<Result<_, _>>()?)
+    }
+}
+
+impl Cached for bool {
+    fn write(&self, w: &mut dyn Write) -> std::io::Result<()> { (*self as u8).write(w) }
+    fn read(r: &mut Reader<'_>) -> anyhow::Result<Self> { Ok(u8::read(r)? != 0) }
+}
+
+impl Cached for () {
+    fn write(&self, _: &mut dyn Write) -> std::io::Result<()> { Ok(()) }
+    fn read(_: &mut Reader<'_>) -> anyhow::Result<Self> { Ok(()) }
+
+    fn read_vec(_: &mut Reader<'_>, n: usize) -> anyhow::Result<Vec<Self>> { Ok(vec![(); n]) }
+}
+
+impl Cached for String {
+    fn write(&self, w: &mut dyn Write) -> std::io::Result<()> {
+        write_bytes(w, self.as_bytes())
+    }
+    fn read(r: &mut Reader<'_>) -> anyhow::Result<Self> {
+        Ok(String::from_utf8(read_bytes(r)?)?)
+    }
+}
+
+impl<T: Cached> Cached for Vec<T> {
+    fn write(&self, w: &mut dyn Write) -> std::io::Result<()> {
+        self.len().write(w)?;
+        T::write_slice(self, w)
+    }
+    fn read(r: &mut Reader<'_>) -> anyhow::Result<Self> {
+        let n = usize::read(r)?;
+        T::read_vec(r, n)
+    }
+}
+
+impl<T: Cached> Cached for Option<T> {
+    fn write(&self, w: &mut dyn Write) -> std::io::Result<()> {
+        self.is_some().write(w)?;
+        self.as_ref().map_or(Ok(()), |x| x.write(w))
+    }
+    fn read(r: &mut Reader<'_>) -> anyhow::Result<Self> {
+        if bool::read(r)? { Ok(Some(T::read(r)?)) } else { Ok(None) }
+    }
+}
+
+impl<A: Cached, B: Cached> Cached for (A, B) {
+    fn write(&self, w: &mut dyn Write) -> std::io::Result<()> {
+        self.0.write(w)?;
+        self.1.write(w)
+    }
+    fn read(r: &mut Reader<'_>) -> anyhow::Result<Self> {
+        Ok((A::read(r)?, B::read(r)?))
+    }
+}
+
+fn bad_tag(what: &str, tag: u8) -> anyhow::Error {
+    anyhow::anyhow!("broken cache: unknown {} {}", what, tag)
+}
+
+impl Cached for vcd::Value {
+    fn write(&self, w: &mut dyn Write) -> std::io::Result<()> {
+        let tag: u8 = match self {
+            vcd::Value::V0 => { 0 }
+            vcd::Value::V1 => { 1 }
+            vcd::Value::X  => { 2 }
+            vcd::Value::Z  => { 3 }
+        };
+        tag.write(w)
+    }
+    fn read(r: &mut Reader<'_>) -> anyhow::Result<Self> {
+        match u8::read(r)? {
+            0   => { Ok(vcd::Value::V0) }
+            1   => { Ok(vcd::Value::V1) }
+            2   => { Ok(vcd::Value::X) }
+            3   => { Ok(vcd::Value::Z) }
+            tag => { Err(bad_tag("value", tag)) }
+        }
+    }
+}
+
+impl Cached for UInt {
+    fn write(&self, w: &mut dyn Write) -> std::io::Result<()> {
+        self.width.write(w)?;
+        self.words.write(w)
+    }
+    fn read(r: &mut Reader<'_>) -> anyhow::Result<Self> {
+        let (width, words) = (usize::read(r)?, Vec::<u64>::read(r)?);
+        // the arithmetic relies on the word count and on the bits above `width` being zero
+        let high = if width % 64 == 0 { 0 } else { !0_u64 << (width % 64) };
+        if words.len() != width.div_ceil(64).max(1) || words.last().is_some_and(|w| w & high != 0) {
+            return Err(anyhow::anyhow!("broken cache: invalid integer of width {}", width));
+        }
+        Ok(UInt{ width, words })
+    }
+}
+
+impl Cached for Bits {
+    fn write(&self, w: &mut dyn Write) -> std::io::Result<()> {
+        match self {
+            Bits::B(b) => { 0_u8.write(w)?; b.write(w) }
+            Bits::V(x) => { 1_u8.write(w)?; x.write(w) }
+            Bits::L(x) => { 2_u8.write(w)?; x.value.write(w)?; x.x.write(w)?; x.z.write(w) }
+            Bits::X    => { 3_u8.write(w) }
+            Bits::Z    => { 4_u8.write(w) }
+        }
+    }
+    fn read(r: &mut Reader<'_>) -> anyhow::Result<Self> {
+        match u8::read(r)? {
+            0   => { Ok(Bits::B(bool::read(r)?)) }
+            1   => { Ok(Bits::V(UInt::read(r)?)) }
+            2   => { Ok(Bits::L(Logic{ value: UInt::read(r)?, x: UInt::read(r)?, z: UInt::read(r)? })) }
+            3   => { Ok(Bits::X) }
+            4   => { Ok(Bits::Z) }
+            tag => { Err(bad_tag("bits", tag)) }
+        }
+    }
+}
+
+impl Cached for PortValue {
+    fn write(&self, w: &mut dyn Write) -> std::io::Result<()> {
+        self.bits.len().write(w)?;
+        for bit in self.bits.iter() {
+            let driver: u8 = match bit.driver {
+                PortDriver::Input    => { 0 }
+                PortDriver::Output   => { 1 }
+                PortDriver::Unknown  => { 2 }
+                PortDriver::Conflict => { 3 }
+            };
+            driver.write(w)?;
+            bit.level.write(w)?;
+            bit.multiple.write(w)?;
+        }
+        self.strength0.write(w)?;
+        self.strength1.write(w)
+    }
+    fn read(r: &mut Reader<'_>) -> anyhow::Result<Self> {
+        let n = usize::read(r)?;
+        let mut bits = Vec::new();
+        for _ in 0..n {
+            let driver = match u8::read(r)? {
+                0   => { PortDriver::Input }
+                1   => { PortDriver::Output }
+                2   => { PortDriver::Unknown }
+                3   => { PortDriver::Conflict }
+                tag => { return Err(bad_tag("port driver", tag)); }
+            };
+            bits.push(PortBit{ driver, level: vcd::Value::read(r)?, multiple: bool::read(r)? });
+        }
+        Ok(PortValue{ bits, strength0: u8::read(r)?, strength1: u8::read(r)? })
+    }
+}
+
+impl Cached for ValueChangeStream {
+    fn write(&self, w: &mut dyn Write) -> std::io::Result<()> {
+        match self {
+            Self::Bits(xs)         => { 0_u8.write(w)?; xs.write(w) }
+            Self::Integer(xs)      => { 1_u8.write(w)?; xs.write(w) }
+            Self::Parameter(xs)    => { 2_u8.write(w)?; xs.write(w) }
+            Self::Event(xs)        => { 3_u8.write(w)?; xs.write(w) }
+            Self::Real(xs)         => { 4_u8.write(w)?; xs.write(w) }
+            Self::String(xs)       => { 5_u8.write(w)?; xs.write(w) }
+            Self::Enum(xs, labels) => { 6_u8.write(w)?; xs.write(w)?; labels.write(w) }
+            Self::Port(xs)         => { 7_u8.write(w)?; xs.write(w) }
+        }
+    }
+    fn read(r: &mut Reader<'_>) -> anyhow::Result<Self> {
+        match u8::read(r)? {
+            0   => { Ok(Self::Bits     (Cached::read(r)?)) }
+            1   => { Ok(Self::Integer  (Cached::read(r)?)) }
+            2   => { Ok(Self::Parameter(Cached::read(r)?)) }
+            3   => { Ok(Self::Event    (Cached::read(r)?)) }
+            4   => { Ok(Self::Real     (Cached::read(r)?)) }
+            5   => { Ok(Self::String   (Cached::read(r)?)) }
+            6   => { Ok(Self::Enum     (Cached::read(r)?, Cached::read(r)?)) }
+            7   => { Ok(Self::Port     (Cached::read(r)?)) }
+            tag => { Err(bad_tag("stream", tag)) }
+        }
+    }
+}
+
+impl Cached for ScopeValue {
+    fn write(&self, w: &mut dyn Write) -> std::io::Result<()> {
+        self.name.write(w)?;
+        self.index.write(w)?;
+        self.width.write(w)?;
+        let range = self.range.map(|range| match range {
+            BitRange::Bit(b)             => { (b, None) }
+            BitRange::Range(left, right) => { (left, Some(right)) }
+        });
+        range.write(w)?;
+        self.order.write(w)?;
+        self.bits.write(w)?;
+        self.render.write(w)?;
+        self.open.write(w)
+    }
+    fn read(r: &mut Reader<'_>) -> anyhow::Result<Self> {
+        let mut value = ScopeValue::new(&String::read(r)?, usize::read(r)?, usize::read(r)?);
+        let range: Option<(i32, Option<i32>)> = Cached::read(r)?;
+        value.range = range.map(|range| match range {
+            (b, None)           => { BitRange::Bit(b) }
+            (left, Some(right)) => { BitRange::Range(left, right) }
+        });
+        value.order  = usize::read(r)?;
+        value.bits   = Vec::read(r)?;
+        value.render = bool::read(r)?;
+        value.open   = bool::read(r)?;
+        Ok(value)
+    }
+}
+
+impl Cached for Scope {
+    fn write(&self, w: &mut dyn Write) -> std::io::Result<()> {
+        self.name.write(w)?;
+        let kind = ScopeKind::ALL.iter().position(|k| *k == self.kind).unwrap() as u8;
+        kind.write(w)?;
+        self.order.write(w)?;
+        self.open.write(w)?;
+        self.items.len().write(w)?;
+        for item in self.items.iter() {
+            match item {
+                ScopeItem::Value(v) => { 0_u8.write(w)?; v.write(w)?; }
+                ScopeItem::Scope(s) => { 1_u8.write(w)?; s.write(w)?; }
+            }
+        }
+        Ok(())
+    }
+    fn read(r: &mut Reader<'_>) -> anyhow::Result<Self> {
+        let name = String::read(r)?;
+        let kind = u8::read(r)?;
+        let kind = *ScopeKind::ALL.get(kind as usize).ok_or_else(|| bad_tag("scope kind", kind))?;
+        let mut scope = Scope::new(&name, kind);
+        scope.order = usize::read(r)?;
+        scope.open  = bool::read(r)?;
+        for _ in 0..usize::read(r)? {
+            match u8::read(r)? {
+                0   => { scope.items.push(ScopeItem::Value(ScopeValue::read(r)?)); }
+                1   => { scope.items.push(ScopeItem::Scope(Scope::read(r)?)); }
+                tag => { return Err(bad_tag("scope item", tag)); }
+            }
+        }
+        Ok(scope)
+    }
+}
+
+fn scope_indices_ok(scope: &Scope, n: usize) -> bool {
+    fn value_ok(v: &ScopeValue, n: usize) -> bool {
+        v.index < n && v.bits.iter().all(|b| value_ok(b, n))
+    }
+    scope.items.iter().all(|item| match item {
+        ScopeItem::Value(v) => { value_ok(v, n) }
+        ScopeItem::Scope(s) => { scope_indices_ok(s, n) }
+    })
+}
+
+impl Cached for TimeSeries {
+    fn write(&self, w: &mut dyn Write) -> std::io::Result<()> {
+        self.scope.write(w)?;
+        self.values.write(w)?;
+        self.time_scale.coef.write(w)?;
+        self.time_scale.unit.name().to_string().write(w)?;
+        self.dump_off.write(w)?;
+        self.warnings.write(w)?;
+        self.buses.len().write(w)?;
+        for bus in self.buses.iter() {
+            bus.index.write(w)?;
+            bus.bits.write(w)?;
+        }
+        self.info.date.write(w)?;
+        self.info.version.write(w)?;
+        self.info.comments.write(w)
+    }
+    fn read(r: &mut Reader<'_>) -> anyhow::Result<Self> {
+        let mut ts = TimeSeries::new();
+        ts.scope  = Scope::read(r)?;
+        ts.values = Arc::new(Vec::read(r)?);
+        let coef = u32::read(r)?;
+        let unit = String::read(r)?;
+        let unit = TimeUnit::from_name(&unit).ok_or_else(|| anyhow::anyhow!("broken cache: unknown unit {}", unit))?;
+        ts.time_scale = TimeScale::new(coef, unit);
+        ts.dump_off = Vec::read(r)?;
+        ts.warnings = Vec::read(r)?;
+        for _ in 0..usize::read(r)? {
+            ts.buses.push(Bus{ index: usize::read(r)?, bits: Vec::read(r)? });
+        }
+        ts.info = FileInfo{ date: Option::read(r)?, version: Option::read(r)?, comments: Vec::read(r)? };
+
+        // the UI indexes the streams with them
+        let n = ts.values.len();
+        let bus_ok = |b: &Bus| b.index < n && b.bits.iter().flatten().all(|i| *i < n);
+        if !scope_indices_ok(&ts.scope, n) || !ts.buses.iter().all(bus_ok) {
+            return Err(anyhow::anyhow!("broken cache: stream index out of range"));
+        }
+        Ok(ts)
+    }
+}
+
+// FNV-1a
+fn hash(mut h: u64, bytes: &[u8]) -> u64 {
+    for b in bytes.iter() {
+        h = (h ^ *b as u64).wrapping_mul(0x100000001b3);
+    }
+    h
+}
+const HASH_INIT: u64 = 0xcbf29ce484222325;
+
+// identifies the source file. a cache is used only if all of them match.
+// hashing a whole multi-gigabyte file takes as long as parsing it, so only its head and tail are.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Key {
+    size: u64,
+    mtime: u64, // in ns
+    hash: u64,
+    lenient: bool, // a lenient load keeps going after errors
+}
+
+impl Key {
+    const HASHED: u64 = 64 * 1024;
+
+    pub fn new(fname: &str, opts: &LoadOptions) -> anyhow::Result<Self> {
+        use std::io::{Read, Seek};
+        let mut f = std::fs::File::open(fname)?;
+        let meta = f.metadata()?;
+        let size = meta.len();
+        let mtime = meta.modified()?.duration_since(std::time::UNIX_EPOCH)?.as_nanos() as u64;
+
+        let mut buf = Vec::new();
+        (&mut f).take(Self::HASHED).read_to_end(&mut buf)?;
+        f.seek(std::io::SeekFrom::Start(size.saturating_sub(Self::HASHED)))?;
+        f.take(Self::HASHED).read_to_end(&mut buf)?;
+
+        Ok(Self{ size, mtime, hash: hash(HASH_INIT, &buf), lenient: opts.lenient })
+    }
+}
+
+impl Cached for Key {
+    fn write(&self, w: &mut dyn Write) -> std::io::Result<()> {
+        self.size.write(w)?;
+        self.mtime.write(w)?;
+        self.hash.write(w)?;
+        self.lenient.write(w)
+    }
+    fn read(r: &mut Reader<'_>) -> anyhow::Result<Self> {
+        Ok(Self{ size: u64::read(r)?, mtime: u64::read(r)?, hash: u64::read(r)?, lenient: bool::read(r)? })
+    }
+}
+
+// `.wave.vcd.<ext>` next to `wave.vcd`, then `~/.cache/tuiwave/<hash of the path>.<ext>`
+fn cache_paths(fname: &str, ext: &str) -> Vec<PathBuf> {
+    let path = Path::new(fname);
+    let mut paths = Vec::new();
+    if let Some(name) = path.file_name() {
+        paths.push(path.with_file_name(format!(".{}.{}", name.to_string_lossy(), ext)));
+    }
+    let dir = std::env::var_os("XDG_CACHE_HOME").map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".cache")));
+    if let Some(dir) = dir {
+        let abs = std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+        let h = hash(HASH_INIT, abs.as_os_str().as_encoded_bytes());
+        paths.push(dir.join("tuiwave").join(format!("{:016x}.{}", h, ext)));
+    }
+    paths
+}
+
+// a cache file of `fname` whose header matches the key
+pub struct MappedCache {
+    mmap: memmap2::Mmap,
+    body: usize, // after the header
+}
+
+impl MappedCache {
+    fn open_at(path: &Path, key: &Key) -> anyhow::Result<Self> {
+        let f = std::fs::File::open(path)?;
+        // SAFETY: a cache file is not modified after it is written. it is written into a
+        // temporary file and then renamed (see `CacheFile`).
+        let mmap = unsafe { memmap2::Mmap::map(&f)? };
+        let mut r = Reader::new(&mmap);
+        if r.bytes(MAGIC.len())? != MAGIC || u32::read(&mut r)? != VERSION || Key::read(&mut r)? != *key {
+            return Err(anyhow::anyhow!("stale cache"));
+        }
+        let body = r.pos;
+        Ok(Self{ mmap, body })
+    }
+
+    pub fn open(fname: &str, ext: &str, key: &Key) -> Option<Self> {
+        cache_paths(fname, ext).iter().find_map(|path| Self::open_at(path, key).ok())
+    }
+
+    pub fn len(&self) -> usize {
+        self.mmap.len()
+    }
+
+    pub fn body(&self) -> Reader<'_> {
+        self.reader_at(self.body)
+    }
+
+    // `pos` is the offset from the beginning of the file, see `CacheFile::position`
+    pub fn reader_at(&self, pos: usize) -> Reader<'_> {
+        Reader{ buf: &self.mmap, pos: pos.min(self.mmap.len()) }
+    }
+}
+
+// a cache file being written. it appears under its name when `finish` is called,
+// so that a reader never sees a partially written file.
+pub struct CacheFile {
+    w: std::io::BufWriter<std::fs::File>,
+    tmp: PathBuf,
+    path: PathBuf,
+    pos: u64,
+}
+
+impl CacheFile {
+    fn create_at(path: &Path, key: &Key) -> anyhow::Result<Self> {
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            std::fs::create_dir_all(dir)?;
+        }
+        let tmp = path.with_extension(format!("tmp{}", std::process::id()));
+        let w = std::io::BufWriter::new(std::fs::File::create(&tmp)?);
+        let mut f = Self{ w, tmp, path: path.to_path_buf(), pos: 0 };
+        f.write_all(MAGIC)?;
+        VERSION.write(&mut f)?;
+        key.write(&mut f)?;
+        Ok(f)
+    }
+
+    // `key` should be taken before loading, so that a file modified while loading is not cached
+    pub fn create(fname: &str, ext: &str, key: &Key) -> anyhow::Result<Self> {
+        let mut last_err = anyhow::anyhow!("no place to write the cache");
+        for path in cache_paths(fname, ext).iter() {
+            match Self::create_at(path, key) {
+                Ok(f)    => { return Ok(f); }
+                Err(err) => { last_err = err; }
+            }
+        }
+        Err(last_err)
+    }
+
+    // the number of bytes written so far
+    pub fn position(&self) -> u64 {
+        self.pos
+    }
+
+    pub fn finish(mut self) -> anyhow::Result<()> {
+        self.w.flush()?;
+        self.w.get_ref().sync_all()?;
+        std::fs::rename(&self.tmp, &self.path)?;
+        Ok(())
+    }
+}
+
+impl Write for CacheFile {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let n = self.w.write(buf)?;
+        self.pos += n as u64;
+        Ok(n)
+    }
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.w.flush()
+    }
+}
+
+impl Drop for CacheFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.tmp); // already renamed if finished
+    }
+}
+
+// None if there is no fresh cache
+pub fn load(fname: &str, key: &Key) -> Option<TimeSeries> {
+    let cache = MappedCache::open(fname, "tuiwave", key)?;
+    let mut r = cache.body();
+    let ts = TimeSeries::read(&mut r).ok()?;
+    r.is_end().then_some(ts)
+}
+
+// stops writing when `canceled` is set. the partial file is removed
+pub fn store(fname: &str, key: &Key, ts: &TimeSeries, canceled: &AtomicBool) -> anyhow::Result<()> {
+    let mut f = CacheFile::create(fname, "tuiwave", key)?;
+    ts.write(&mut Cancelable{ w: &mut f, canceled })?;
+    f.finish()
+}
+
+struct Cancelable<'a, W> {
+    w: W,
+    canceled: &'a AtomicBool,
+}
+
+impl<W: Write> Write for Cancelable<'_, W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        if self.canceled.load(Ordering::Relaxed) {
+            // not `Interrupted`, which `write_all` retries
+            return Err(std::io::Error::other("canceled"));
+        }
+        self.w.write(buf)
+    }
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.w.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const VCD: &str = "$date today $end
+$version test $end
+$timescale 10ps $end
+$scope module top $end
+$var wire 1 ! clk $end
+$var wire 8 \" data $end
+$var integer 32 # n $end
+$var real 64 $ r $end
+$var event 1 % ev $end
+$var string 1 & msg $end
+$scope module sub $end
+$var wire 1 ' bus[0] $end
+$var wire 1 ( bus[1] $end
+$upscope $end
+$upscope $end
+$enddefinitions $end
+#0
+0!
+bx \"
+b101 #
+r0.5 $
+sidle &
+0'
+1(
+#5
+1!
+b1x01 \"
+1%
+sbusy &
+#10
+0!
+b11111111 \"
+b11111111111111111111111111111111 #
+r-2.25 $
+1'
+";
+
+    fn parse() -> TimeSeries {
+        crate::load_vcd::load_vcd(VCD.as_bytes(), &LoadOptions::default()).unwrap()
+    }
+
+    fn encode(ts: &TimeSeries) -> Vec<u8> {
+        let mut buf = Vec::new();
+        ts.write(&mut buf).unwrap();
+        buf
+    }
+
+    #[test]
+    fn time_series_round_trip() {
+        let ts = parse();
+        assert!(!ts.buses.is_empty());
+        let buf = encode(&ts);
+        let mut r = Reader::new(&buf);
+        assert_eq!(TimeSeries::read(&mut r).unwrap(), ts);
+        assert!(r.is_end());
+    }
+
+    #[test]
+    fn numbers_are_copied_at_once() {
+        let xs: Vec<usize> = (0..20000).map(|i| i * 12345).collect();
+        let mut buf = Vec::new();
+        xs.write(&mut buf).unwrap();
+        assert_eq!(buf.len(), 8 + 8 * xs.len());
+        assert_eq!(Vec::<usize>::read(&mut Reader::new(&buf)).unwrap(), xs);
+        assert!(Vec::<u64>::read(&mut Reader::new(&buf[..buf.len() - 1])).is_err());
+    }
+
+    #[test]
+    fn broken_cache_is_rejected() {
+        let ts = parse();
+
+        // a value pointing at a stream that does not exist
+        let mut broken = ts.clone();
+        let ScopeItem::Scope(top) = &mut broken.scope.items[0] else { panic!("no top scope") };
+        let ScopeItem::Value(v) = &mut top.items[0] else { panic!("no value") };
+        v.index = 100;
+        assert!(TimeSeries::read(&mut Reader::new(&encode(&broken))).is_err());
+
+        // integers with a wrong word count or with bits set above their width
+        for (words, width) in [(vec![], 8), (vec![0, 0], 8), (vec![0], 65), (vec![0x100], 8), (vec![0, 2], 65)] {
+            let mut buf = Vec::new();
+            UInt{ words, width }.write(&mut buf).unwrap();
+            assert!(UInt::read(&mut Reader::new(&buf)).is_err());
+        }
+        let mut buf = Vec::new();
+        UInt{ words: vec![!0, 1], width: 65 }.write(&mut buf).unwrap();
+        assert!(UInt::read(&mut Reader::new(&buf)).is_ok());
+
+        // every truncation and any flipped byte must be an error or a consistent value, not a panic
+        let buf = encode(&ts);
+        for n in 0..buf.len() {
+            assert!(TimeSeries::read(&mut Reader::new(&buf[..n])).is_err());
+        }
+        for i in 0..buf.len() {
+            let mut bytes = buf.clone();
+            bytes[i] ^= 0x5a;
+            if let Ok(ts) = TimeSeries::read(&mut Reader::new(&bytes)) {
+                let _ = ts.stats();
+                for v in ts.values.iter() {
+                    if let ValueChangeStream::Bits(xs) = v {
+                        let _ = xs.iter_from(0).count();
+                        let _ = xs.any_unknown(0, xs.len());
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn store_and_load() {
+        let dir = std::env::temp_dir().join(format!("tuiwave-cache-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let fname = dir.join("t.vcd").to_string_lossy().to_string();
+        std::fs::write(&fname, VCD).unwrap();
+        let key = Key::new(&fname, &LoadOptions::default()).unwrap();
+        let ts = parse();
+
+        assert!(store(&fname, &key, &ts, &AtomicBool::new(true)).is_err()); // canceled
+        assert!(load(&fname, &key).is_none());
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1); // no temporary file is left
+
+        store(&fname, &key, &ts, &AtomicBool::new(false)).unwrap();
+        assert_eq!(load(&fname, &key), Some(ts));
+        let lenient = Key::new(&fname, &LoadOptions{ lenient: true, ..LoadOptions::default() }).unwrap();
+        assert!(load(&fname, &lenient).is_none());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
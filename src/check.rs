@@ -0,0 +1,183 @@
+use crate::timeseries::*;
+use crate::load_vcd::{self, VcdParser};
+
+use std::collections::*;
+
+// `tuiwave check file.vcd` walks the whole file with the loader in lenient mode and reports
+// every problem, including what the viewer accepts silently (see `VcdLoader::check`).
+
+// problems in `$scope` and `$var` declarations. the parser does not keep their line numbers,
+// so they are taken from the order of appearance (see HeaderRewriter).
+struct HeaderChecker<'a> {
+    scope_lines: std::slice::Iter<'a, u64>,
+    var_lines: std::slice::Iter<'a, u64>,
+    widths: HashMap<vcd::IdCode, (u32, u64)>, // id -> (declared width, line)
+    problems: Vec<(u64, String)>,
+}
+
+impl HeaderChecker<'_> {
+    fn check_scope(&mut self, items: &[vcd::ScopeItem], path: &str) {
+        let mut scopes = HashMap::new(); // name -> line
+        let mut vars = HashMap::new();
+        for item in items.iter() {
+            match item {
+                vcd::ScopeItem::Scope(s) => {
+                    let line = self.scope_lines.next().copied().unwrap_or(0);
+                    let name = format!("{}{}", path, s.identifier);
+                    if let Some(first) = scopes.insert(s.identifier.clone(), line) {
+                        self.problems.push((line, format!("duplicate scope {} (first declared at line {})", name, first)));
+                    }
+                    self.check_scope(&s.items, &format!("{}.", name));
+                }
+                vcd::ScopeItem::Var(v) => {
+                    let line = self.var_lines.next().copied().unwrap_or(0);
+                    let index = v.index.map(|i| i.to_string()).unwrap_or_default();
+                    let name = format!("{}{}{}", path, v.reference, index);
+                    if let Some(first) = vars.insert(format!("{}{}", v.reference, index), line) {
+                        self.problems.push((line, format!("duplicate variable {} (first declared at line {})", name, first)));
+                    }
+                    let range_width = match v.index {
+                        Some(vcd::ReferenceIndex::BitSelect(_))        => { Some(1) }
+                        Some(vcd::ReferenceIndex::Range(left, right)) => { Some(left.abs_diff(right) + 1) }
+                        None                                           => { None }
+                    };
+                    if range_width.is_some_and(|w| w != v.size) {
+                        self.problems.push((line, format!("{} is declared with {} bits", name, v.size)));
+                    }
+                    match self.widths.entry(v.code) {
+                        hash_map::Entry::Occupied(e) if e.get().0 != v.size => {
+                            let (width, first) = *e.get();
+                            self.problems.push((line, format!("{} shares id code {} with a {}-bit variable at line {}, but has {} bits",
+                                                              name, v.code, width, first, v.size)));
+                        }
+                        hash_map::Entry::Occupied(_) => {}
+                        hash_map::Entry::Vacant(e) => { e.insert((v.size, line)); }
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+fn check_header<R: std::io::BufRead>(parser: &mut VcdParser<R>, header: &vcd::Header) -> Vec<(u64, String)> {
+    let reader = parser.reader().get_ref();
+    let mut checker = HeaderChecker{
+        scope_lines: reader.scope_lines.iter(),
+        var_lines: reader.var_lines.iter(),
+        widths: HashMap::new(),
+        problems: Vec::new(),
+    };
+    checker.check_scope(&header.items, "");
+    checker.problems.sort_by_key(|(line, _)| *line);
+    checker.problems
+}
+
+// prints the problems as they are found and returns the number of them
+pub fn check_vcd<R: std::io::BufRead>(src: R, out: &mut impl std::io::Write) -> anyhow::Result<usize> {
+    const CLEAR_EVERY: usize = 1 << 20; // commands. changes are not kept, only checked
+
+    let mut parser = load_vcd::new_parser(src);
+    let header = match load_vcd::parse_vcd_header(&mut parser) {
+        Ok(header) => { header }
+        Err(e) => {
+            writeln!(out, "{}", e)?;
+            return Ok(1);
+        }
+    };
+    let mut n_problems = 0;
+    for (line, problem) in check_header(&mut parser, &header).iter() {
+        writeln!(out, "line {}: {}", line, problem)?;
+        n_problems += 1;
+    }
+
+    let opts = LoadOptions{ lenient: true, ..LoadOptions::default() };
+    let reader = parser.reader().get_ref();
+    let (mut ts, mut loader) = load_vcd::make_loader(&header, &reader.kinds, &reader.var_types, &opts)?;
+    loader.check = true;
+
+    let mut n_commands = 0;
+    let mut error_line = None; // the parser may fail on every character of a malformed record
+    while let Some(cmd) = parser.next() {
+        let at = load_vcd::location(&mut parser);
+        let is_io_error = cmd.as_ref().is_err_and(|e| e.kind() != std::io::ErrorKind::InvalidData);
+        if cmd.is_err() && !is_io_error && error_line.replace(at.line) == Some(at.line) {
+            continue;
+        }
+        loader.apply(&mut ts, cmd, at)?;
+        for warning in ts.warnings.drain(..) {
+            writeln!(out, "{}", warning)?;
+            n_problems += 1;
+        }
+        if is_io_error {
+            break; // e.g. unexpected end of file. the parser cannot go further
+        }
+        n_commands += 1;
+        if n_commands % CLEAR_EVERY == 0 {
+            ts.values_mut().iter_mut().for_each(|v| v.clear());
+        }
+    }
+    Ok(n_problems)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const VCD: &str = "$timescale 1ns $end
+$scope module top $end
+$var wire 1 ! a $end
+$var wire 1 ! a $end
+$var wire 4 \" b [7:0] $end
+$var wire 2 ! c $end
+$scope module s $end
+$upscope $end
+$scope module s $end
+$upscope $end
+$upscope $end
+$enddefinitions $end
+#0
+0!
+#10
+q!
+1!
+#5
+0!
+";
+
+    fn check(vcd: &str) -> (usize, Vec<String>) {
+        let mut out = Vec::new();
+        let n = check_vcd(vcd.as_bytes(), &mut out).unwrap();
+        (n, String::from_utf8(out).unwrap().lines().map(|l| l.to_string()).collect())
+    }
+
+    #[test]
+    fn reports_each_problem_once() {
+        let (n, lines) = check(VCD);
+        assert_eq!(lines, [
+            "line 4: duplicate variable top.a (first declared at line 3)",
+            "line 5: top.b[7:0] is declared with 4 bits",
+            "line 6: top.c shares id code ! with a 1-bit variable at line 3, but has 2 bits",
+            "line 9: duplicate scope top.s (first declared at line 7)",
+            "line 16 (byte 249): unexpected character at start of command",
+            "line 18 (byte 257): timestamp goes back from 10 ns to 5 ns",
+        ]);
+        assert_eq!(n, 6);
+    }
+
+    #[test]
+    fn clean_file() {
+        let vcd = "$timescale 1ns $end\n$scope module top $end\n$var wire 4 ! b [3:0] $end\n$upscope $end\n$enddefinitions $end\n#0\nb0 !\n#10\nb1x !\n";
+        assert_eq!(check(vcd), (0, vec![]));
+    }
+
+    #[test]
+    fn reports_every_problem() {
+        let mut vcd = "$scope module top $end\n$var wire 1 ! a $end\n$upscope $end\n$enddefinitions $end\n".to_string();
+        for t in 0..250 {
+            vcd += &format!("#{}\n1?\n", t);
+        }
+        let (n, lines) = check(&vcd);
+        assert_eq!((n, lines.len()), (250, 250));
+    }
+}
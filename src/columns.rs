@@ -0,0 +1,644 @@
+use crate::timeseries::{Bits, Logic, UInt};
+use crate::cache::{self, Cached, Reader};
+
+use std::collections::*;
+
+// compact storage of value changes. times are delta encoded and values are packed per type.
+// every BLOCK-th entry is sampled, so that a lookup by index decodes at most BLOCK entries.
+
+const BLOCK: usize = 64;
+
+fn push_varint(buf: &mut Vec<u8>, mut x: u64) {
+    while 0x80 <= x {
+        buf.push((x as u8) | 0x80);
+        x >>= 7;
+    }
+    buf.push(x as u8);
+}
+
+// for data from a cache file, which may be broken
+fn read_varint_checked(buf: &[u8], pos: &mut usize) -> Option<u64> {
+    let mut x = 0;
+    for shift in (0..64).step_by(7) {
+        let b = *buf.get(*pos)?;
+        *pos += 1;
+        x |= ((b & 0x7f) as u64) << shift;
+        if b < 0x80 {
+            return Some(x);
+        }
+    }
+    None
+}
+
+fn read_varint(buf: &[u8], pos: &mut usize) -> u64 {
+    let mut x = 0;
+    let mut shift = 0;
+    loop {
+        let b = buf[*pos];
+        *pos += 1;
+        x |= ((b & 0x7f) as u64) << shift;
+        if b < 0x80 {
+            return x;
+        }
+        shift += 7;
+    }
+}
+
+// times of changes in increasing order
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct TimeColumn {
+    samples: Vec<(u64, usize)>, // (time, offset in `deltas`) of changes 0, BLOCK, 2 * BLOCK, ...
+    deltas: Vec<u8>,            // varint differences from the previous change
+    len: usize,
+    last: u64,
+}
+
+pub struct TimeIter<'a> {
+    column: &'a TimeColumn,
+    i: usize,
+    t: u64,
+    pos: usize,
+}
+
+impl Iterator for TimeIter<'_> {
+    type Item = u64;
+    fn next(&mut self) -> Option<u64> {
+        if self.column.len <= self.i {
+            return None;
+        }
+        if self.i.is_multiple_of(BLOCK) {
+            (self.t, self.pos) = self.column.samples[self.i / BLOCK];
+        } else {
+            self.t += read_varint(&self.column.deltas, &mut self.pos);
+        }
+        self.i += 1;
+        Some(self.t)
+    }
+}
+
+impl TimeColumn {
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn last(&self) -> Option<u64> {
+        if self.len == 0 { None } else { Some(self.last) }
+    }
+
+    // `t` must not be less than the last one. it is clamped if it is
+    pub fn push(&mut self, t: u64) {
+        debug_assert!(self.len == 0 || self.last <= t, "time goes back from {} to {}", self.last, t);
+        let t = if self.len == 0 { t } else { t.max(self.last) };
+        if self.len.is_multiple_of(BLOCK) {
+            self.samples.push((t, self.deltas.len()));
+        } else {
+            push_varint(&mut self.deltas, t - self.last);
+        }
+        self.last = t;
+        self.len += 1;
+    }
+
+    // whether the samples and the deltas agree, so that decoding does not fail
+    fn is_consistent(&self) -> bool {
+        if self.samples.len() != self.len.div_ceil(BLOCK) {
+            return false;
+        }
+        let (mut t, mut pos) = (0_u64, 0);
+        for i in 0..self.len {
+            if i.is_multiple_of(BLOCK) {
+                let (sample, offset) = self.samples[i / BLOCK];
+                if offset != pos || (0 < i && sample < t) {
+                    return false;
+                }
+                t = sample;
+            } else {
+                let Some(t_next) = read_varint_checked(&self.deltas, &mut pos).and_then(|d| t.checked_add(d)) else {
+                    return false;
+                };
+                t = t_next;
+            }
+        }
+        pos == self.deltas.len() && (self.len == 0 || t == self.last)
+    }
+
+    pub fn iter_from(&self, i: usize) -> TimeIter<'_> {
+        let start = i / BLOCK * BLOCK;
+        let mut iter = TimeIter{ column: self, i: start, t: 0, pos: 0 };
+        for _ in start..i.min(self.len) {
+            iter.next();
+        }
+        iter
+    }
+
+    // the first index whose time is >= t
+    pub fn lower_bound(&self, t: u64) -> usize {
+        let b = self.samples.partition_point(|(s, _)| *s < t);
+        if b == 0 {
+            return 0;
+        }
+        let start = (b - 1) * BLOCK;
+        start + self.iter_from(start).take(BLOCK).take_while(|x| *x < t).count()
+    }
+
+    // the first index whose time is > t
+    pub fn upper_bound(&self, t: u64) -> usize {
+        let b = self.samples.partition_point(|(s, _)| *s <= t);
+        if b == 0 {
+            return 0;
+        }
+        let start = (b - 1) * BLOCK;
+        start + self.iter_from(start).take(BLOCK).take_while(|x| *x <= t).count()
+    }
+}
+
+// values of changes. `pop` removes the value pushed last, and can only be called once
+// before the next `push` (a change at the same time replaces the last one).
+pub trait ValueColumn<T>: std::fmt::Debug + Clone + PartialEq + Default {
+    fn len(&self) -> usize;
+    fn push(&mut self, v: T);
+    fn pop(&mut self);
+    fn iter_from(&self, i: usize) -> impl Iterator<Item = T> + '_;
+
+    fn get(&self, i: usize) -> T {
+        self.iter_from(i).next().unwrap()
+    }
+    fn last(&self) -> Option<T> {
+        self.len().checked_sub(1).map(|i| self.get(i))
+    }
+}
+
+// reals, events and ports are kept as they are
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlainColumn<T>(Vec<T>);
+
+impl<T> Default for PlainColumn<T> {
+    fn default() -> Self {
+        Self(Vec::new())
+    }
+}
+
+impl<T: std::fmt::Debug + Clone + PartialEq> ValueColumn<T> for PlainColumn<T> {
+    fn len(&self) -> usize {
+        self.0.len()
+    }
+    fn push(&mut self, v: T) {
+        self.0.push(v);
+    }
+    fn pop(&mut self) {
+        self.0.pop();
+    }
+    fn iter_from(&self, i: usize) -> impl Iterator<Item = T> + '_ {
+        self.0[i.min(self.0.len())..].iter().cloned()
+    }
+    fn get(&self, i: usize) -> T {
+        self.0[i].clone()
+    }
+}
+
+// strings are interned. a simulation usually prints the same messages many times
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct StringColumn {
+    ids: Vec<u32>,
+    strings: Vec<String>,
+    lookup: HashMap<String, u32>,
+}
+
+impl ValueColumn<String> for StringColumn {
+    fn len(&self) -> usize {
+        self.ids.len()
+    }
+    fn push(&mut self, v: String) {
+        let strings = &mut self.strings;
+        let id = *self.lookup.entry(v).or_insert_with_key(|v| {
+            strings.push(v.clone());
+            (strings.len() - 1) as u32
+        });
+        self.ids.push(id);
+    }
+    fn pop(&mut self) {
+        self.ids.pop();
+    }
+    fn iter_from(&self, i: usize) -> impl Iterator<Item = String> + '_ {
+        self.ids[i.min(self.ids.len())..].iter().map(|id| self.strings[*id as usize].clone())
+    }
+    fn get(&self, i: usize) -> String {
+        self.strings[self.ids[i] as usize].clone()
+    }
+}
+
+// 2 bits per change: 0, 1, X, or OTHER. Z and vectors (OTHER) are appended to `others`
+// as a tag, the width and the little endian bytes of the value (and the X and Z masks).
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct BitsColumn {
+    codes: Vec<u64>,     // 32 changes per word
+    len: usize,
+    ranks: Vec<usize>,   // the number of OTHERs before changes 0, BLOCK, 2 * BLOCK, ...
+    others: Vec<u8>,
+    samples: Vec<usize>, // offsets in `others` of OTHERs 0, BLOCK, 2 * BLOCK, ...
+    n_others: usize,
+    last_offset: usize,  // of the last OTHER, to pop it
+}
+
+impl BitsColumn {
+    const B0: u64 = 0;
+    const B1: u64 = 1;
+    const X: u64 = 2;
+    const OTHER: u64 = 3;
+
+    const TAG_Z: u8 = 0;
+    const TAG_V: u8 = 1;
+    const TAG_L: u8 = 2;
+
+    fn code(&self, i: usize) -> u64 {
+        (self.codes[i / 32] >> (2 * (i % 32))) & 3
+    }
+
+    // the number of OTHERs before change i
+    fn rank(&self, i: usize) -> usize {
+        let start = i / BLOCK * BLOCK;
+        self.ranks[i / BLOCK] + (start..i).filter(|j| self.code(*j) == Self::OTHER).count()
+    }
+
+    // the offset of the r-th OTHER
+    fn other_offset(&self, r: usize) -> usize {
+        let mut pos = self.samples[r / BLOCK];
+        for _ in 0..r % BLOCK {
+            self.read_other(&mut pos);
+        }
+        pos
+    }
+
+    fn push_uint(&mut self, x: &UInt) {
+        let bytes = x.words.iter().flat_map(|w| w.to_le_bytes());
+        self.others.extend(bytes.take(x.width.div_ceil(8)));
+    }
+
+    fn read_uint(&self, width: usize, pos: &mut usize) -> UInt {
+        let n = width.div_ceil(8);
+        let mut x = UInt::zero(width);
+        for (k, b) in self.others[*pos..*pos + n].iter().enumerate() {
+            x.words[k / 8] |= (*b as u64) << (8 * (k % 8));
+        }
+        *pos += n;
+        x
+    }
+
+    // the end of the OTHER at `pos`. None if it runs over the end
+    fn other_end(&self, mut pos: usize) -> Option<usize> {
+        let tag = *self.others.get(pos)?;
+        pos += 1;
+        let n_uints = match tag {
+            Self::TAG_Z => { return Some(pos); }
+            Self::TAG_V => { 1 }
+            Self::TAG_L => { 3 }
+            _           => { return None; }
+        };
+        let width = read_varint_checked(&self.others, &mut pos)?;
+        let n = usize::try_from(width.div_ceil(8)).ok()?.checked_mul(n_uints)?;
+        pos.checked_add(n).filter(|end| *end <= self.others.len())
+    }
+
+    // whether the codes, the ranks and the OTHERs agree, so that decoding does not fail
+    fn is_consistent(&self) -> bool {
+        if self.codes.len() != self.len.div_ceil(32) || self.ranks.len() != self.len.div_ceil(BLOCK) {
+            return false;
+        }
+        let unused = 2 * (self.len % 32);
+        if 0 < unused && self.codes.last().is_some_and(|w| w >> unused != 0) {
+            return false; // `push` ORs the next code in
+        }
+        let mut n_others = 0;
+        for i in 0..self.len {
+            if i.is_multiple_of(BLOCK) && self.ranks[i / BLOCK] != n_others {
+                return false;
+            }
+            if self.code(i) == Self::OTHER {
+                n_others += 1;
+            }
+        }
+        if n_others != self.n_others || self.samples.len() != n_others.div_ceil(BLOCK) {
+            return false;
+        }
+        let (mut pos, mut last) = (0, 0);
+        for r in 0..n_others {
+            if r.is_multiple_of(BLOCK) && self.samples[r / BLOCK] != pos {
+                return false;
+            }
+            last = pos;
+            match self.other_end(pos) {
+                Some(end) => { pos = end; }
+                None      => { return false; }
+            }
+        }
+        // `last_offset` is used only when the last change is an OTHER (see `last`)
+        let last_is_other = 0 < self.len && self.code(self.len - 1) == Self::OTHER;
+        pos == self.others.len() && (!last_is_other || last == self.last_offset)
+    }
+
+    fn read_other(&self, pos: &mut usize) -> Bits {
+        let tag = self.others[*pos];
+        *pos += 1;
+        if tag == Self::TAG_Z {
+            return Bits::Z;
+        }
+        let width = read_varint(&self.others, pos) as usize;
+        let value = self.read_uint(width, pos);
+        if tag == Self::TAG_V {
+            return Bits::V(value);
+        }
+        let x = self.read_uint(width, pos);
+        let z = self.read_uint(width, pos);
+        Bits::L(Logic{ value, x, z })
+    }
+}
+
+pub struct BitsIter<'a> {
+    column: &'a BitsColumn,
+    i: usize,
+    pos: usize, // of the next OTHER
+}
+
+impl Iterator for BitsIter<'_> {
+    type Item = Bits;
+    fn next(&mut self) -> Option<Bits> {
+        if self.column.len <= self.i {
+            return None;
+        }
+        let code = self.column.code(self.i);
+        self.i += 1;
+        match code {
+            BitsColumn::B0 => { Some(Bits::B(false)) }
+            BitsColumn::B1 => { Some(Bits::B(true)) }
+            BitsColumn::X  => { Some(Bits::X) }
+            _              => { Some(self.column.read_other(&mut self.pos)) }
+        }
+    }
+}
+
+impl ValueColumn<Bits> for BitsColumn {
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn push(&mut self, v: Bits) {
+        if self.len.is_multiple_of(BLOCK) {
+            self.ranks.push(self.n_others);
+        }
+        if self.len.is_multiple_of(32) {
+            self.codes.push(0);
+        }
+        let code = match v {
+            Bits::B(false) => { Self::B0 }
+            Bits::B(true)  => { Self::B1 }
+            Bits::X        => { Self::X }
+            _              => { Self::OTHER }
+        };
+        if code == Self::OTHER {
+            if self.n_others.is_multiple_of(BLOCK) {
+                self.samples.push(self.others.len());
+            }
+            self.last_offset = self.others.len();
+            self.n_others += 1;
+            match &v {
+                Bits::V(x) => {
+                    self.others.push(Self::TAG_V);
+                    push_varint(&mut self.others, x.width as u64);
+                    self.push_uint(x);
+                }
+                Bits::L(x) => {
+                    self.others.push(Self::TAG_L);
+                    push_varint(&mut self.others, x.value.width as u64);
+                    self.push_uint(&x.value);
+                    self.push_uint(&x.x);
+                    self.push_uint(&x.z);
+                }
+                _ => { self.others.push(Self::TAG_Z); }
+            }
+        }
+        *self.codes.last_mut().unwrap() |= code << (2 * (self.len % 32));
+        self.len += 1;
+    }
+
+    fn pop(&mut self) {
+        let Some(i) = self.len.checked_sub(1) else {
+            return;
+        };
+        if self.code(i) == Self::OTHER {
+            self.others.truncate(self.last_offset);
+            self.n_others -= 1;
+            if self.n_others.is_multiple_of(BLOCK) {
+                self.samples.pop();
+            }
+            self.last_offset = match self.n_others {
+                0 => { 0 }
+                n => { self.other_offset(n - 1) }
+            };
+        }
+        self.codes[i / 32] &= !(3 << (2 * (i % 32)));
+        if i.is_multiple_of(32) {
+            self.codes.pop();
+        }
+        if i.is_multiple_of(BLOCK) {
+            self.ranks.pop();
+        }
+        self.len = i;
+    }
+
+    fn iter_from(&self, i: usize) -> impl Iterator<Item = Bits> + '_ {
+        let i = i.min(self.len);
+        let r = if i < self.len { self.rank(i) } else { self.n_others };
+        let pos = if r < self.n_others { self.other_offset(r) } else { self.others.len() };
+        BitsIter{ column: self, i, pos }
+    }
+
+    fn last(&self) -> Option<Bits> {
+        let i = self.len.checked_sub(1)?;
+        match self.code(i) {
+            Self::OTHER => { Some(self.read_other(&mut self.last_offset.clone())) }
+            _           => { self.iter_from(i).next() }
+        }
+    }
+}
+
+impl Cached for TimeColumn {
+    fn write(&self, w: &mut dyn std::io::Write) -> std::io::Result<()> {
+        self.samples.write(w)?;
+        cache::write_bytes(w, &self.deltas)?;
+        self.len.write(w)?;
+        self.last.write(w)
+    }
+    fn read(r: &mut Reader<'_>) -> anyhow::Result<Self> {
+        let column = Self{ samples: Vec::read(r)?, deltas: cache::read_bytes(r)?, len: usize::read(r)?, last: u64::read(r)? };
+        if !column.is_consistent() {
+            return Err(anyhow::anyhow!("broken cache: inconsistent time column"));
+        }
+        Ok(column)
+    }
+}
+
+impl<T: Cached> Cached for PlainColumn<T> {
+    fn write(&self, w: &mut dyn std::io::Write) -> std::io::Result<()> {
+        self.0.write(w)
+    }
+    fn read(r: &mut Reader<'_>) -> anyhow::Result<Self> {
+        Ok(Self(Vec::read(r)?))
+    }
+}
+
+impl Cached for StringColumn {
+    fn write(&self, w: &mut dyn std::io::Write) -> std::io::Result<()> {
+        self.ids.write(w)?;
+        self.strings.write(w)
+    }
+    fn read(r: &mut Reader<'_>) -> anyhow::Result<Self> {
+        let ids: Vec<u32> = Vec::read(r)?;
+        let strings: Vec<String> = Vec::read(r)?;
+        if ids.iter().any(|id| strings.len() <= *id as usize) {
+            return Err(anyhow::anyhow!("broken cache: string id out of range"));
+        }
+        let lookup = strings.iter().enumerate().map(|(i, s)| (s.clone(), i as u32)).collect();
+        Ok(Self{ ids, strings, lookup })
+    }
+}
+
+impl Cached for BitsColumn {
+    fn write(&self, w: &mut dyn std::io::Write) -> std::io::Result<()> {
+        self.codes.write(w)?;
+        self.len.write(w)?;
+        self.ranks.write(w)?;
+        cache::write_bytes(w, &self.others)?;
+        self.samples.write(w)?;
+        self.n_others.write(w)?;
+        self.last_offset.write(w)
+    }
+    fn read(r: &mut Reader<'_>) -> anyhow::Result<Self> {
+        let column = Self{
+            codes: Vec::read(r)?,
+            len: usize::read(r)?,
+            ranks: Vec::read(r)?,
+            others: cache::read_bytes(r)?,
+            samples: Vec::read(r)?,
+            n_others: usize::read(r)?,
+            last_offset: usize::read(r)?,
+        };
+        if !column.is_consistent() {
+            return Err(anyhow::anyhow!("broken cache: inconsistent bits column"));
+        }
+        Ok(column)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_bits(i: usize) -> Bits {
+        match i % 7 {
+            0 => { Bits::B(false) }
+            1 => { Bits::B(true) }
+            2 => { Bits::X }
+            3 => { Bits::Z }
+            4 => { Bits::V(UInt::new(i as u64 * 2654435761, 40)) }
+            5 => {
+                let mut x = Logic::new(UInt::new(i as u64, 70));
+                x.x.set_bit(3, true);
+                x.z.set_bit(69, true);
+                Bits::L(x)
+            }
+            _ => { Bits::V(UInt::new(i as u64, 8)) }
+        }
+    }
+
+    #[test]
+    fn time_column_round_trip() {
+        let times: Vec<u64> = (0..1000).map(|i| i * i / 3 + (i % 5) * 1000).scan(0, |t, d| { *t += d; Some(*t) }).collect();
+        let mut column = TimeColumn::default();
+        for t in times.iter() {
+            column.push(*t);
+        }
+        assert_eq!(column.len(), times.len());
+        assert_eq!(column.last(), times.last().copied());
+        for i in [0, 1, 63, 64, 65, 500, 999, 1000] {
+            assert_eq!(column.iter_from(i).collect::<Vec<_>>(), times[i..]);
+        }
+        for t in [0, 1, times[64], times[64] + 1, times[999], u64::MAX] {
+            assert_eq!(column.lower_bound(t), times.partition_point(|x| *x < t));
+            assert_eq!(column.upper_bound(t), times.partition_point(|x| *x <= t));
+        }
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "time goes back")]
+    fn time_column_rejects_going_back() {
+        let mut column = TimeColumn::default();
+        column.push(10);
+        column.push(5);
+    }
+
+    #[test]
+    #[cfg(not(debug_assertions))]
+    fn time_column_clamps_going_back() {
+        let mut column = TimeColumn::default();
+        column.push(10);
+        column.push(5);
+        assert_eq!(column.iter_from(0).collect::<Vec<_>>(), [10, 10]);
+    }
+
+    #[test]
+    fn bits_column_round_trip() {
+        let mut column = BitsColumn::default();
+        let mut expected = Vec::new();
+        for i in 0..500 {
+            column.push(sample_bits(i));
+            expected.push(sample_bits(i));
+            if i % 11 == 0 {
+                // a change at the same time replaces the last one
+                column.pop();
+                expected.pop();
+                column.push(sample_bits(i + 4));
+                expected.push(sample_bits(i + 4));
+            }
+        }
+        assert_eq!(column.len(), expected.len());
+        assert_eq!(column.last(), expected.last().cloned());
+        for i in [0, 1, 31, 32, 63, 64, 65, 128, 333, 499, 500] {
+            assert_eq!(column.iter_from(i).collect::<Vec<_>>(), expected[i..]);
+        }
+        for (i, x) in expected.iter().enumerate() {
+            assert_eq!(column.get(i), *x);
+        }
+    }
+
+    #[test]
+    fn bits_column_pop_to_empty() {
+        let mut column = BitsColumn::default();
+        column.push(sample_bits(4));
+        column.pop();
+        assert_eq!(column, BitsColumn::default());
+    }
+
+    #[test]
+    fn bits_column_pops_others() {
+        let values: Vec<Bits> = (0..2 * BLOCK as u64 + 3).map(|i| Bits::V(UInt::new(i * 1000, 20))).collect();
+        let mut column = BitsColumn::default();
+        values.iter().for_each(|v| column.push(v.clone()));
+        for n in (0..values.len()).rev() {
+            column.pop();
+            assert!(column.is_consistent());
+            assert_eq!(column.last(), n.checked_sub(1).map(|i| values[i].clone()));
+        }
+        assert_eq!(column, BitsColumn::default());
+    }
+
+    #[test]
+    fn string_column_interns() {
+        let mut column = StringColumn::default();
+        for s in ["a", "b", "a", "c", "a"] {
+            column.push(s.to_string());
+        }
+        column.pop();
+        assert_eq!(column.iter_from(0).collect::<Vec<_>>(), ["a", "b", "a", "c"]);
+        assert_eq!(column.strings.len(), 3);
+    }
+}
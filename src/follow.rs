@@ -0,0 +1,222 @@
+use crate::timeseries::*;
+use crate::bus;
+use crate::cache;
+use crate::load_vcd::{self, Location, VcdLoader};
+
+use std::io::Read;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{mpsc, Arc};
+
+// a reader that waits for more data at the end of file instead of returning EOF.
+// it is for a file that is still being written by a running simulation.
+pub struct TailReader {
+    file: std::fs::File,
+}
+
+impl TailReader {
+    pub fn new(file: std::fs::File) -> Self {
+        Self{ file }
+    }
+}
+
+impl Read for TailReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        loop {
+            let n = self.file.read(buf)?;
+            if n != 0 || buf.is_empty() {
+                return Ok(n);
+            }
+            std::thread::sleep(std::time::Duration::from_millis(100));
+        }
+    }
+}
+
+// bytes read from a file by the loader thread, for the progress gauge
+#[derive(Clone, Debug)]
+pub struct Progress {
+    read: Arc<AtomicU64>,
+    pub total: u64,
+    started: std::time::Instant,
+}
+
+impl Progress {
+    pub fn new(total: u64) -> Self {
+        Self{ read: Arc::new(AtomicU64::new(0)), total, started: std::time::Instant::now() }
+    }
+
+    pub fn read(&self) -> u64 {
+        self.read.load(Ordering::Relaxed)
+    }
+
+    pub fn elapsed(&self) -> std::time::Duration {
+        self.started.elapsed()
+    }
+
+    // counts bytes read from the file. wrap the file before decompressing it
+    pub fn reader<R: Read>(&self, inner: R) -> ProgressReader<R> {
+        ProgressReader{ inner, read: self.read.clone() }
+    }
+}
+
+pub struct ProgressReader<R> {
+    inner: R,
+    read: Arc<AtomicU64>,
+}
+
+impl<R: Read> Read for ProgressReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.read.fetch_add(n as u64, Ordering::Relaxed);
+        Ok(n)
+    }
+}
+
+// parses value changes in a background thread and appends them to the TimeSeries
+// every time `poll` is called.
+pub struct Follower {
+    loader: VcdLoader,
+    receiver: mpsc::Receiver<(std::io::Result<vcd::Command>, Location)>,
+    location: Location, // of the last applied command
+    finished: bool,
+    canceled: bool,
+    cache: Option<CacheWriter>,
+}
+
+// writes the cache when the whole file is loaded
+struct CacheWriter {
+    fname: String,
+    key: cache::Key,
+    scope: Scope, // as in the header. the UI sorts the tree
+    thread: Option<std::thread::JoinHandle<anyhow::Result<()>>>,
+    canceled: Arc<AtomicBool>,
+}
+
+impl CacheWriter {
+    // written in the background, so that the viewer can go on. the streams are not copied
+    // but shared, as they do not change once loading is complete
+    fn start(&mut self, ts: &TimeSeries) {
+        let mut ts = ts.clone();
+        ts.scope = self.scope.clone();
+        let (fname, key, canceled) = (self.fname.clone(), self.key.clone(), self.canceled.clone());
+        self.thread = Some(std::thread::spawn(move || cache::store(&fname, &key, &ts, &canceled)));
+    }
+
+    // returns the error of a finished thread
+    fn join(&mut self, wait: bool) -> Option<anyhow::Error> {
+        if !wait && !self.thread.as_ref().is_some_and(|t| t.is_finished()) {
+            return None;
+        }
+        match self.thread.take()?.join() {
+            Ok(result) => { result.err() }
+            Err(_)     => { Some(anyhow::anyhow!("the thread panicked")) }
+        }
+    }
+}
+
+impl Follower {
+    // max number of commands applied per poll, not to freeze the UI
+    const MAX_COMMANDS_PER_POLL: usize = 100_000;
+
+    // it blocks until the header is available
+    pub fn start<R: std::io::BufRead + Send + 'static>(src: R, opts: &LoadOptions) -> anyhow::Result<(TimeSeries, Self)> {
+        let mut parser = load_vcd::new_parser(src);
+        let (ts, loader) = load_vcd::load_vcd_header(&mut parser, opts)?;
+
+        // bounded, so that the parser does not run far ahead of the viewer and fill the memory
+        let (sender, receiver) = mpsc::sync_channel(Self::MAX_COMMANDS_PER_POLL);
+        std::thread::spawn(move || {
+            while let Some(cmd) = parser.next() {
+                let at = load_vcd::location(&mut parser);
+                if sender.send((cmd, at)).is_err() {
+                    break; // the viewer is closed
+                }
+            }
+        });
+        Ok((ts, Self{ loader, receiver, location: Location::default(), finished: false, canceled: false, cache: None }))
+    }
+
+    // the cache is written when the whole file is loaded. `ts` is the one returned by `start`
+    pub fn write_cache(&mut self, fname: &str, key: cache::Key, ts: &TimeSeries) {
+        self.cache = Some(CacheWriter{ fname: fname.to_string(), key, scope: ts.scope.clone(), thread: None,
+                                       canceled: Arc::new(AtomicBool::new(false)) });
+    }
+
+    // stops writing the cache if it is not done yet. call it before exiting
+    pub fn stop(&mut self, ts: &mut TimeSeries) {
+        if let Some(writer) = &mut self.cache {
+            if let Some(e) = writer.join(false) {
+                ts.warnings.push(format!("failed to write the cache: {}", e));
+            }
+            writer.canceled.store(true, Ordering::Relaxed);
+            writer.join(true);
+        }
+    }
+
+    pub fn current_time(&self) -> u64 {
+        self.loader.current_t.min(self.loader.to)
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    // reached the end of the stream without being canceled
+    pub fn is_complete(&self) -> bool {
+        self.finished && !self.canceled && !self.loader.is_past_window()
+    }
+
+    // stops loading and keeps what is loaded so far.
+    // the thread stops when it fails to send the next command.
+    pub fn cancel(&mut self, ts: &mut TimeSeries) {
+        if self.finished {
+            return;
+        }
+        self.finished = true;
+        self.canceled = true;
+        self.receiver = mpsc::channel().1;
+        ts.warnings.push(format!("loading is canceled at line {} (byte {})", self.location.line, self.location.offset));
+    }
+
+    // returns true if something is appended
+    pub fn poll(&mut self, ts: &mut TimeSeries) -> anyhow::Result<bool> {
+        if let Some(writer) = &mut self.cache {
+            if let Some(e) = writer.join(false) {
+                ts.warnings.push(format!("failed to write the cache: {}", e));
+            }
+        }
+        if self.finished {
+            return Ok(false);
+        }
+        let mut updated = false;
+        for _ in 0..Self::MAX_COMMANDS_PER_POLL {
+            match self.receiver.try_recv() {
+                Ok((cmd, at)) => {
+                    self.loader.apply(ts, cmd, at)?;
+                    self.location = at;
+                    updated = true;
+                    if self.loader.is_past_window() {
+                        self.finished = true;
+                        self.receiver = mpsc::channel().1; // stops the thread
+                        break;
+                    }
+                }
+                Err(mpsc::TryRecvError::Empty) => {
+                    break;
+                }
+                Err(mpsc::TryRecvError::Disconnected) => {
+                    self.finished = true; // reached the end of stream (e.g. pipe is closed)
+                    break;
+                }
+            }
+        }
+        if updated {
+            bus::update_buses(ts);
+        }
+        if self.is_complete() {
+            if let Some(writer) = &mut self.cache {
+                writer.start(ts);
+            }
+        }
+        Ok(updated)
+    }
+}
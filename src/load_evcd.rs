@@ -0,0 +1,333 @@
+use crate::timeseries::*;
+use crate::load_vcd::{LoadError, Location, Warnings};
+
+use std::collections::*;
+use std::sync::Arc;
+
+// extended VCD (IEEE 1364 18.4) has `$var port` and value changes with driving strength,
+// `pDUD 6 0 <3`. the vcd crate does not know them, so it is parsed here.
+
+// the header is scanned up to the first `$var`. it may start with a long `$comment` or `$date`.
+pub fn is_evcd<R: std::io::BufRead>(src: R) -> std::io::Result<bool> {
+    const MAX_BYTES: usize = 64 << 20; // not a text file
+
+    let (mut prev, mut in_text, mut read) = (String::new(), false, 0);
+    for line in src.split(b'\n') {
+        let line = line?;
+        read += line.len() + 1;
+        for token in String::from_utf8_lossy(&line).split_whitespace() {
+            if in_text {
+                in_text = token != "$end";
+                continue;
+            }
+            match token {
+                _ if prev.is_empty() && !token.starts_with('$') => { return Ok(false); } // e.g. FST
+                _ if prev == "$var"               => { return Ok(token == "port"); }
+                "$comment" | "$date" | "$version" => { in_text = true; }
+                "$dumpports"                      => { return Ok(true); }
+                "$enddefinitions"                 => { return Ok(false); }
+                _ => {}
+            }
+            prev = token.to_string();
+        }
+        if MAX_BYTES < read {
+            break;
+        }
+    }
+    Ok(false)
+}
+
+// `[7:0]` or `[3]`
+fn parse_range(s: &str) -> Option<BitRange> {
+    let s = s.strip_prefix('[')?.strip_suffix(']')?;
+    match s.split_once(':') {
+        Some((left, right)) => { Some(BitRange::Range(left.trim().parse().ok()?, right.trim().parse().ok()?)) }
+        None                => { Some(BitRange::Bit(s.trim().parse().ok()?)) }
+    }
+}
+
+struct EvcdLoader {
+    ts: TimeSeries,
+    scopes: Vec<Scope>,
+    map: HashMap<String, (usize, usize)>, // id -> (index, width)
+    names: HashMap<String, String>, // id -> full path, for error messages
+    current_t: u64,
+    lenient: bool,
+    warnings: Warnings,
+}
+
+impl EvcdLoader {
+    fn error(&mut self, at: Location, signal: Option<&str>, message: String) -> Result<(), LoadError> {
+        let signal = signal.map(|id| self.names.get(id).cloned().unwrap_or_else(|| id.to_string()));
+        let err = LoadError{ location: at, signal, message };
+        if self.lenient {
+            self.warnings.push(&mut self.ts, err.to_string());
+            Ok(())
+        } else {
+            Err(err)
+        }
+    }
+
+    // a command from `$xxx` to `$end`, or a timestamp / value change
+    fn apply(&mut self, tokens: &[String], at: Location) -> Result<(), LoadError> {
+        let Some(head) = tokens.first() else {
+            return Ok(());
+        };
+        match head.as_str() {
+            "$timescale" => {
+                let ts: String = tokens[1..].iter().filter(|x| *x != "$end").cloned().collect();
+                let split = ts.find(|c: char| !c.is_ascii_digit()).unwrap_or(ts.len());
+                match (ts[..split].parse(), TimeUnit::from_name(&ts[split..])) {
+                    (Ok(coef), Some(unit)) => { self.ts.time_scale = TimeScale::new(coef, unit); }
+                    _ => { return self.error(at, None, format!("invalid timescale {}", ts)); }
+                }
+            }
+            "$scope" => {
+                let kind = tokens.get(1).map(|s| ScopeKind::from_vcd_name(s)).unwrap_or(ScopeKind::Other);
+                let name = tokens.get(2).cloned().unwrap_or_default();
+                self.scopes.push(Scope::new(&name, kind));
+            }
+            "$upscope" if 1 < self.scopes.len() => {
+                let scope = self.scopes.pop().unwrap();
+                self.scopes.last_mut().unwrap().add_scope(scope);
+            }
+            "$var" => {
+                // $var port 1 <0 name $end, $var port [7:0] <1 name $end
+                if tokens.len() < 5 {
+                    return self.error(at, None, "invalid $var".to_string());
+                }
+                let (size, id, name) = (&tokens[2], &tokens[3], &tokens[4]);
+                let mut range = tokens.get(5).and_then(|s| parse_range(s));
+                let width = match parse_range(size) {
+                    Some(BitRange::Range(left, right)) => {
+                        range = range.or(Some(BitRange::Range(left, right)));
+                        (left - right).unsigned_abs() as usize + 1
+                    }
+                    Some(BitRange::Bit(_)) => { 1 }
+                    None => { size.parse().unwrap_or(1) }
+                };
+                if width == 0 {
+                    return self.error(at, None, format!("zero-width port {}", name));
+                }
+                let values = Arc::make_mut(&mut self.ts.values);
+                let (idx, _) = *self.map.entry(id.clone()).or_insert_with(|| {
+                    values.push(ValueChangeStream::Port(ValueChangeStreamImpl::new()));
+                    (values.len() - 1, width)
+                });
+                let path: Vec<&str> = self.scopes[1..].iter().map(|s| s.name.as_str()).collect();
+                self.names.entry(id.clone()).or_insert_with(|| format!("{}.{}", path.join("."), name));
+
+                let mut value = ScopeValue::new(name, idx, width);
+                value.range = range;
+                self.scopes.last_mut().unwrap().add_value(value);
+            }
+            "$dumpportsoff" if self.ts.dump_off.last().is_none_or(|(_, to)| *to != u64::MAX) => {
+                self.ts.dump_off.push((self.current_t, u64::MAX));
+            }
+            "$dumpportson" => {
+                if let Some((_, to)) = self.ts.dump_off.last_mut().filter(|(_, to)| *to == u64::MAX) {
+                    *to = self.current_t;
+                }
+            }
+            _ if head.starts_with('#') => {
+                match head[1..].parse() {
+                    Ok(t) if t < self.current_t => {
+                        return self.error(at, None, format!("timestamp goes back from #{} to #{}", self.current_t, t));
+                    }
+                    Ok(t)  => { self.current_t = t; }
+                    Err(_) => { return self.error(at, None, format!("invalid timestamp {}", head)); }
+                }
+            }
+            _ if head.starts_with('p') => {
+                // p<states> <strength0> <strength1> <id>
+                if tokens.len() != 4 {
+                    return self.error(at, None, format!("invalid port value change {}", tokens.join(" ")));
+                }
+                let id = &tokens[3];
+                let Some(&(idx, width)) = self.map.get(id) else {
+                    return self.error(at, None, format!("undeclared id code {}", id));
+                };
+                let bits: Option<Vec<PortBit>> = head[1..].chars().map(PortBit::from_evcd_char).collect();
+                let (Some(bits), Ok(strength0), Ok(strength1)) = (bits, tokens[1].parse::<u8>(), tokens[2].parse::<u8>()) else {
+                    return self.error(at, Some(id), format!("invalid port value {}", tokens.join(" ")));
+                };
+                // one state per bit. so a value is never empty, which `to_bits` cannot represent
+                if bits.len() != width {
+                    return self.error(at, Some(id), format!("{} states for a {}-bit port: {}", bits.len(), width, tokens.join(" ")));
+                }
+                if 7 < strength0.max(strength1) {
+                    return self.error(at, Some(id), format!("strength out of 0..=7: {}", tokens.join(" ")));
+                }
+                if let ValueChangeStream::Port(xs) = &mut self.ts.values_mut()[idx] {
+                    xs.push_change(self.current_t, PortValue{ bits, strength0, strength1 });
+                }
+            }
+            "$date" | "$version" | "$comment" => {
+                let text = tokens[1..].iter().take_while(|x| *x != "$end").cloned().collect::<Vec<_>>().join(" ");
+                match head.as_str() {
+                    "$date"    => { self.ts.info.date = Some(text); }
+                    "$version" => { self.ts.info.version = Some(text); }
+                    _          => { self.ts.info.comments.push(text); }
+                }
+            }
+            _ => {
+                // $dumpports, $end, ...
+            }
+        }
+        Ok(())
+    }
+}
+
+// a command continues until `$end`. a value change takes 4 tokens.
+fn is_complete(tokens: &[String]) -> bool {
+    let Some(head) = tokens.first() else {
+        return false;
+    };
+    if head.starts_with('$') {
+        matches!(head.as_str(), "$dumpports" | "$dumpportsall" | "$dumpportsoff" | "$dumpportson" | "$end")
+            || tokens.last().is_some_and(|x| x == "$end")
+    } else if head.starts_with('p') {
+        tokens.len() == 4
+    } else {
+        true
+    }
+}
+
+pub fn load_evcd<R: std::io::BufRead>(mut src: R, opts: &LoadOptions) -> anyhow::Result<TimeSeries> {
+    let mut loader = EvcdLoader{
+        ts: TimeSeries::new(),
+        scopes: Vec::new(),
+        map: HashMap::new(),
+        names: HashMap::new(),
+        current_t: 0,
+        lenient: opts.lenient,
+        warnings: Warnings::default(),
+    };
+    loader.scopes.push(Scope::new(&loader.ts.scope.name, loader.ts.scope.kind));
+
+    let mut at = Location{ line: 0, offset: 0 };
+    let mut tokens: Vec<String> = Vec::new();
+    let mut line = Vec::new();
+    loop {
+        line.clear();
+        let n = src.read_until(b'\n', &mut line)?;
+        if n == 0 {
+            break;
+        }
+        at.line += 1;
+        for token in String::from_utf8_lossy(&line).split_whitespace() {
+            tokens.push(token.to_string());
+            if is_complete(&tokens) {
+                loader.apply(&tokens, at)?;
+                tokens.clear();
+            }
+        }
+        at.offset += n as u64;
+    }
+
+    while 1 < loader.scopes.len() {
+        let scope = loader.scopes.pop().unwrap();
+        loader.scopes.last_mut().unwrap().add_scope(scope);
+    }
+    let mut ts = loader.ts;
+    ts.scope = loader.scopes.pop().unwrap();
+    opts.warn_unsupported(&mut ts, "EVCD");
+    Ok(ts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EVCD: &str = "$comment
+  a long comment mentioning $var wire 1 ! x $end
+$end
+$timescale 1ns $end
+$scope module top $end
+$var port 1 <0 clk $end
+$var port [3:0] <1 bus $end
+$upscope $end
+$enddefinitions $end
+#0
+$dumpports
+pD 6 0 <0
+pUDZL 6 6 <1
+$end
+#10
+pU 3 3 <0
+pAUDD 6 6 <1
+";
+
+    fn ports(ts: &TimeSeries, i: usize) -> Vec<(u64, PortValue)> {
+        let ValueChangeStream::Port(xs) = &ts.values[i] else { panic!("not a port") };
+        xs.iter_from(0).map(|c| (c.time, c.new_value)).collect()
+    }
+
+    #[test]
+    fn detects_evcd_after_a_long_header() {
+        let padding = format!("$comment {} $end\n", "x ".repeat(100_000));
+        assert!(is_evcd(format!("{}{}", padding, EVCD).as_bytes()).unwrap());
+        assert!(!is_evcd("$comment $var port $end\n$scope module top $end\n$var wire 1 ! clk $end\n".as_bytes()).unwrap());
+        assert!(!is_evcd(&b"\x00\x01binary"[..]).unwrap());
+    }
+
+    #[test]
+    fn decodes_ports() {
+        let ts = load_evcd(EVCD.as_bytes(), &LoadOptions::default()).unwrap();
+        assert_eq!(ts.time_scale, TimeScale::new(1, TimeUnit::Ns));
+
+        let clk = ports(&ts, 0);
+        assert_eq!(clk.len(), 2);
+        assert_eq!(clk[0].1.driver(), PortDriver::Input);
+        assert_eq!(clk[0].1.to_bits(), Bits::B(false));
+        assert_eq!((clk[1].0, clk[1].1.to_bits()), (10, Bits::B(true)));
+        assert!(clk[1].1.is_weak());
+
+        let bus = ports(&ts, 1);
+        assert_eq!(bus[0].1.driver(), PortDriver::Unknown); // both sides drive some bits
+        assert_eq!(bus[0].1.to_bits().to_radix_string(Radix::Bin), "10Z0");
+        assert_eq!(bus[1].1.driver(), PortDriver::Conflict);
+    }
+
+    #[test]
+    fn timestamp_going_back() {
+        let src = EVCD.replace("#10", "#10\n#5");
+        let err = load_evcd(src.as_bytes(), &LoadOptions::default()).unwrap_err();
+        assert!(err.to_string().contains("timestamp goes back from #10 to #5"), "{}", err);
+
+        let opts = LoadOptions{ lenient: true, ..LoadOptions::default() };
+        let ts = load_evcd(src.as_bytes(), &opts).unwrap();
+        assert_eq!(ts.warnings.len(), 1);
+        assert_eq!(ports(&ts, 0)[1].0, 10);
+    }
+
+    #[test]
+    fn invalid_port_values() {
+        for (change, message) in [("pUD 6 0 <0", "top.clk: 2 states for a 1-bit port"),
+                                  ("pUD 6 0 <1", "top.bus: 2 states for a 4-bit port"),
+                                  ("p 6 0 <0", "top.clk: 0 states for a 1-bit port"),
+                                  ("pDDDD 8 0 <1", "top.bus: strength out of 0..=7")] {
+            let src = EVCD.replace("pU 3 3 <0", change);
+            let err = load_evcd(src.as_bytes(), &LoadOptions::default()).unwrap_err();
+            assert!(err.to_string().contains(message), "{}", err);
+        }
+
+        let src = EVCD.replace("$var port 1 <0", "$var port 0 <0");
+        let err = load_evcd(src.as_bytes(), &LoadOptions::default()).unwrap_err();
+        assert!(err.to_string().contains("zero-width port clk"), "{}", err);
+
+        let src = EVCD.replace("pU 3 3 <0", "pUD 6 0 <0\npDDDD 9 9 <1");
+        let ts = load_evcd(src.as_bytes(), &LoadOptions{ lenient: true, ..LoadOptions::default() }).unwrap();
+        assert_eq!(ts.warnings.len(), 2);
+        assert_eq!(ports(&ts, 0).len(), 1);
+        assert_eq!(ports(&ts, 1).len(), 2);
+    }
+
+    #[test]
+    fn warnings_are_capped() {
+        let src = format!("{}{}", EVCD, (11..211).map(|t| format!("#{}\npD 6 0 <9\n", t)).collect::<String>());
+        let ts = load_evcd(src.as_bytes(), &LoadOptions{ lenient: true, ..LoadOptions::default() }).unwrap();
+        assert_eq!(ts.warnings.len(), 101);
+        assert_eq!(ts.warnings[100], "100 more warnings are not shown");
+    }
+}
@@ -0,0 +1,154 @@
+use crate::timeseries::*;
+use crate::bus;
+
+use fst_reader::{FstFilter, FstHierarchyEntry, FstReader, FstScopeType, FstSignalValue, FstVarType};
+
+use std::collections::*;
+use std::sync::Arc;
+
+// FST stores the timescale as 10^exponent [s]. convert it to coef * unit
+fn time_scale_from_exponent(exponent: i8) -> TimeScale {
+    let exponent = exponent as i32;
+    let units = [TimeUnit::S, TimeUnit::Ms, TimeUnit::Us, TimeUnit::Ns, TimeUnit::Ps, TimeUnit::Fs, TimeUnit::As];
+    for unit in units {
+        let e = unit.exponent().unwrap();
+        if e <= exponent {
+            return TimeScale::new(10_u32.pow((exponent - e) as u32), unit);
+        }
+    }
+    TimeScale::new(1, TimeUnit::Zs)
+}
+
+fn scope_kind(tpe: FstScopeType) -> ScopeKind {
+    match tpe {
+        FstScopeType::Module           => { ScopeKind::Module    }
+        FstScopeType::Task             => { ScopeKind::Task      }
+        FstScopeType::Function         => { ScopeKind::Function  }
+        FstScopeType::Begin            => { ScopeKind::Begin     }
+        FstScopeType::Fork             => { ScopeKind::Fork      }
+        FstScopeType::Generate         => { ScopeKind::Generate  }
+        FstScopeType::Struct           => { ScopeKind::Struct    }
+        FstScopeType::Union            => { ScopeKind::Union     }
+        FstScopeType::Class            => { ScopeKind::Class     }
+        FstScopeType::Interface        => { ScopeKind::Interface }
+        FstScopeType::Package          => { ScopeKind::Package   }
+        FstScopeType::Program          => { ScopeKind::Program   }
+        FstScopeType::VhdlArchitecture => { ScopeKind::Module    }
+        FstScopeType::VhdlProcedure    => { ScopeKind::Task      }
+        FstScopeType::VhdlFunction     => { ScopeKind::Function  }
+        FstScopeType::VhdlRecord       => { ScopeKind::Struct    }
+        FstScopeType::VhdlProcess      => { ScopeKind::Begin     }
+        FstScopeType::VhdlBlock        => { ScopeKind::Begin     }
+        FstScopeType::VhdlForGenerate  => { ScopeKind::Generate  }
+        FstScopeType::VhdlIfGenerate   => { ScopeKind::Generate  }
+        FstScopeType::VhdlGenerate     => { ScopeKind::Generate  }
+        FstScopeType::VhdlPackage      => { ScopeKind::Package   }
+        _                              => { ScopeKind::Other     }
+    }
+}
+
+fn new_stream(tpe: FstVarType) -> ValueChangeStream {
+    if tpe.is_real() {
+        ValueChangeStream::Real(ValueChangeStreamImpl::new())
+    } else if tpe == FstVarType::GenericString {
+        ValueChangeStream::String(ValueChangeStreamImpl::new())
+    } else if tpe == FstVarType::Event {
+        ValueChangeStream::Event(ValueChangeStreamImpl::new())
+    } else if tpe == FstVarType::Parameter {
+        ValueChangeStream::Parameter(ValueChangeStreamImpl::new())
+    } else if matches!(tpe, FstVarType::Integer | FstVarType::Int | FstVarType::ShortInt |
+                            FstVarType::LongInt | FstVarType::Byte | FstVarType::Time) {
+        ValueChangeStream::Integer(ValueChangeStreamImpl::new())
+    } else {
+        // all the other types are stored as a sequence of bits
+        ValueChangeStream::Bits(ValueChangeStreamImpl::new())
+    }
+}
+
+pub fn load_fst<R: std::io::BufRead + std::io::Seek>(src: R, opts: &LoadOptions) -> anyhow::Result<TimeSeries> {
+
+    let mut reader = FstReader::open(src)?;
+
+    let mut ts = TimeSeries::new();
+    let header = reader.get_header();
+    ts.time_scale = time_scale_from_exponent(header.timescale_exponent);
+    ts.info.date    = Some(header.date.trim().to_string()).filter(|s| !s.is_empty());
+    ts.info.version = Some(header.version.trim().to_string()).filter(|s| !s.is_empty());
+
+    // aliased variables share the same handle, so they also share the stream
+    let mut map: HashMap<usize, usize> = HashMap::new();
+    let mut names: HashMap<usize, String> = HashMap::new(); // handle -> full path, for error messages
+    let mut scopes = vec![Scope::new(&ts.scope.name, ts.scope.kind)];
+    let values = Arc::make_mut(&mut ts.values);
+
+    reader.read_hierarchy(|entry| {
+        match entry {
+            FstHierarchyEntry::Scope{tpe, name, ..} => {
+                scopes.push(Scope::new(&name, scope_kind(tpe)));
+            }
+            FstHierarchyEntry::UpScope if 1 < scopes.len() => {
+                let scope = scopes.pop().unwrap();
+                scopes.last_mut().unwrap().add_scope(scope);
+            }
+            FstHierarchyEntry::Var{tpe, name, length, handle, ..} => {
+                let idx = *map.entry(handle.get_index()).or_insert_with(|| {
+                    values.push(new_stream(tpe));
+                    values.len() - 1
+                });
+                names.entry(handle.get_index()).or_insert_with(|| {
+                    let path: Vec<&str> = scopes[1..].iter().map(|s| s.name.as_str()).collect();
+                    format!("{}.{}", path.join("."), name)
+                });
+                scopes.last_mut().unwrap().add_value(ScopeValue::new(&name, idx, length as usize));
+            }
+            _ => {
+                // attributes and comments are not used
+            }
+        }
+    })?;
+
+    // close scopes that are not closed by $upscope
+    while 1 < scopes.len() {
+        let scope = scopes.pop().unwrap();
+        scopes.last_mut().unwrap().add_scope(scope);
+    }
+    ts.scope = scopes.pop().unwrap();
+    bus::group_bit_blasted(&mut ts);
+
+    let mut type_errors = BTreeSet::new();
+    let values = Arc::make_mut(&mut ts.values);
+    reader.read_signals(&FstFilter::all(), |t, handle, value| {
+        let Some(idx) = map.get(&handle.get_index()) else {
+            return;
+        };
+        match (&mut values[*idx], value) {
+            (ValueChangeStream::Bits(xs) | ValueChangeStream::Integer(xs) | ValueChangeStream::Parameter(xs),
+             FstSignalValue::String(v)) => {
+                xs.push_change(t, Bits::from_ascii(v));
+            }
+            (ValueChangeStream::Event(xs), FstSignalValue::String(_)) => {
+                xs.push_change(t, ());
+            }
+            (ValueChangeStream::Real(xs), FstSignalValue::Real(v)) => {
+                xs.push_change(t, v);
+            }
+            (ValueChangeStream::String(xs), FstSignalValue::String(v)) => {
+                xs.push_change(t, String::from_utf8_lossy(v).to_string());
+            }
+            _ => {
+                type_errors.insert(handle.get_index());
+            }
+        }
+    })?;
+
+    for handle in type_errors {
+        let msg = format!("{}: type error: value does not match the declared type", names[&handle]);
+        if !opts.lenient {
+            return Err(anyhow::anyhow!(msg));
+        }
+        ts.warnings.push(msg);
+    }
+    bus::update_buses(&mut ts);
+    opts.warn_unsupported(&mut ts, "FST");
+    Ok(ts)
+}
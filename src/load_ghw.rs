@@ -0,0 +1,157 @@
+use crate::timeseries::*;
+use crate::bus;
+
+use wellen::{Hierarchy, ScopeOrVarRef, SignalValue, TimescaleUnit};
+
+use std::collections::*;
+use std::sync::Arc;
+
+fn time_scale_from_wellen(hierarchy: &Hierarchy) -> TimeScale {
+    let Some(ts) = hierarchy.timescale() else {
+        return TimeScale::new(1, TimeUnit::Fs); // GHDL always uses fs
+    };
+    let unit = match ts.unit {
+        TimescaleUnit::ZeptoSeconds => { TimeUnit::Zs }
+        TimescaleUnit::AttoSeconds  => { TimeUnit::As }
+        TimescaleUnit::FemtoSeconds => { TimeUnit::Fs }
+        TimescaleUnit::PicoSeconds  => { TimeUnit::Ps }
+        TimescaleUnit::NanoSeconds  => { TimeUnit::Ns }
+        TimescaleUnit::MicroSeconds => { TimeUnit::Us }
+        TimescaleUnit::MilliSeconds => { TimeUnit::Ms }
+        TimescaleUnit::Seconds      => { TimeUnit::S  }
+        TimescaleUnit::Unknown      => { TimeUnit::Tau }
+    };
+    TimeScale::new(ts.factor, unit)
+}
+
+fn scope_kind(tpe: wellen::ScopeType) -> ScopeKind {
+    match tpe {
+        wellen::ScopeType::Module           => { ScopeKind::Module    }
+        wellen::ScopeType::Task             => { ScopeKind::Task      }
+        wellen::ScopeType::Function         => { ScopeKind::Function  }
+        wellen::ScopeType::Begin            => { ScopeKind::Begin     }
+        wellen::ScopeType::Fork             => { ScopeKind::Fork      }
+        wellen::ScopeType::Generate         => { ScopeKind::Generate  }
+        wellen::ScopeType::Struct           => { ScopeKind::Struct    }
+        wellen::ScopeType::Union            => { ScopeKind::Union     }
+        wellen::ScopeType::Class            => { ScopeKind::Class     }
+        wellen::ScopeType::Interface        => { ScopeKind::Interface }
+        wellen::ScopeType::Package          => { ScopeKind::Package   }
+        wellen::ScopeType::Program          => { ScopeKind::Program   }
+        wellen::ScopeType::VhdlArchitecture => { ScopeKind::Module    }
+        wellen::ScopeType::VhdlProcedure    => { ScopeKind::Task      }
+        wellen::ScopeType::VhdlFunction     => { ScopeKind::Function  }
+        wellen::ScopeType::VhdlRecord       => { ScopeKind::Struct    }
+        wellen::ScopeType::VhdlProcess      => { ScopeKind::Begin     }
+        wellen::ScopeType::VhdlBlock        => { ScopeKind::Begin     }
+        wellen::ScopeType::VhdlForGenerate  => { ScopeKind::Generate  }
+        wellen::ScopeType::VhdlIfGenerate   => { ScopeKind::Generate  }
+        wellen::ScopeType::VhdlGenerate     => { ScopeKind::Generate  }
+        wellen::ScopeType::VhdlPackage      => { ScopeKind::Package   }
+        _                                   => { ScopeKind::Other     }
+    }
+}
+
+fn new_stream(hierarchy: &Hierarchy, var: &wellen::Var) -> ValueChangeStream {
+    if var.is_real() {
+        ValueChangeStream::Real(ValueChangeStreamImpl::new())
+    } else if var.is_string() {
+        ValueChangeStream::String(ValueChangeStreamImpl::new())
+    } else if var.signal_encoding() == wellen::SignalEncoding::Event {
+        ValueChangeStream::Event(ValueChangeStreamImpl::new())
+    } else if let Some((_, mapping)) = var.enum_type(hierarchy) {
+        let literals = mapping.iter()
+            .map(|(bits, name)| (Bits::from_ascii(bits.as_bytes()), name.to_string()))
+            .collect();
+        ValueChangeStream::Enum(ValueChangeStreamImpl::new(), literals)
+    } else {
+        ValueChangeStream::Bits(ValueChangeStreamImpl::new())
+    }
+}
+
+fn append_to_scope(scope: &mut Scope, values: &mut Vec<ValueChangeStream>,
+                   map: &mut HashMap<wellen::SignalRef, usize>,
+                   hierarchy: &Hierarchy, items: impl Iterator<Item = ScopeOrVarRef>) {
+    for item in items {
+        match item {
+            ScopeOrVarRef::Var(v) => {
+                let var = &hierarchy[v];
+                // records and arrays are flattened, so slices share the same signal
+                let idx = *map.entry(var.signal_ref()).or_insert_with(|| {
+                    values.push(new_stream(hierarchy, var));
+                    values.len() - 1
+                });
+                let width = var.length().unwrap_or(1) as usize;
+                scope.add_value(ScopeValue::new(var.name(hierarchy), idx, width));
+            }
+            ScopeOrVarRef::Scope(s) => {
+                let s = &hierarchy[s];
+                let mut subscope = Scope::new(s.name(hierarchy), scope_kind(s.scope_type()));
+                append_to_scope(&mut subscope, values, map, hierarchy, s.items(hierarchy));
+                scope.add_scope(subscope);
+            }
+        }
+    }
+}
+
+pub fn load_ghw(fname: &str, opts: &LoadOptions) -> anyhow::Result<TimeSeries> {
+
+    let mut waveform = wellen::simple::read(fname)?;
+
+    let mut ts = TimeSeries::new();
+    let mut map = HashMap::new();
+
+    let hierarchy = waveform.hierarchy();
+    ts.time_scale = time_scale_from_wellen(hierarchy);
+    ts.info.date    = Some(hierarchy.date().trim().to_string()).filter(|s| !s.is_empty());
+    ts.info.version = Some(hierarchy.version().trim().to_string()).filter(|s| !s.is_empty());
+    append_to_scope(&mut ts.scope, Arc::make_mut(&mut ts.values), &mut map, hierarchy, hierarchy.items());
+    bus::group_bit_blasted(&mut ts);
+
+    let signals: Vec<_> = map.keys().copied().collect();
+    waveform.load_signals(&signals);
+
+    // signal -> full path, for error messages
+    let names: HashMap<wellen::SignalRef, String> = waveform.hierarchy().iter_vars()
+        .map(|v| (v.signal_ref(), v.full_name(waveform.hierarchy())))
+        .collect();
+
+    'signals: for (signal_ref, idx) in map.iter() {
+        let signal = waveform.get_signal(*signal_ref)
+            .ok_or_else(|| anyhow::anyhow!("{}: signal not found", names[signal_ref]))?;
+
+        for (time_idx, value) in signal.iter_changes() {
+            let t = waveform.time_table()[time_idx as usize];
+            match (&mut ts.values_mut()[*idx], value) {
+                (ValueChangeStream::Real(xs), SignalValue::Real(v)) => {
+                    xs.push_change(t, v);
+                }
+                (ValueChangeStream::String(xs), SignalValue::String(v)) => {
+                    xs.push_change(t, v.to_string());
+                }
+                (ValueChangeStream::Event(xs), SignalValue::Event) => {
+                    xs.push_change(t, ());
+                }
+                (ValueChangeStream::Bits(xs), v @ (SignalValue::Binary(..) | SignalValue::FourValue(..) | SignalValue::NineValue(..))) => {
+                    let bits = v.to_bit_string().unwrap_or_default();
+                    xs.push_change(t, Bits::from_ascii(bits.as_bytes()));
+                }
+                (ValueChangeStream::Enum(xs, _), v @ (SignalValue::Binary(..) | SignalValue::FourValue(..) | SignalValue::NineValue(..))) => {
+                    let bits = v.to_bit_string().unwrap_or_default();
+                    xs.push_change(t, Bits::from_ascii(bits.as_bytes()));
+                }
+                _ => {
+                    let msg = format!("{}: type error: value does not match the declared type", names[signal_ref]);
+                    if !opts.lenient {
+                        return Err(anyhow::anyhow!(msg));
+                    }
+                    ts.warnings.push(msg);
+                    continue 'signals; // skip the rest of this signal
+                }
+            }
+        }
+    }
+    bus::update_buses(&mut ts);
+    opts.warn_unsupported(&mut ts, "GHW");
+    Ok(ts)
+}
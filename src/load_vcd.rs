@@ -1,30 +1,48 @@
 use crate::timeseries::*;
+use crate::bus;
 // use crate::log::dump;
 
-use anyhow::Context;
-
 use std::collections::*;
+use std::sync::Arc;
 
-pub fn append_to_scope(scope: &mut Scope, values: &mut Vec<ValueChangeStream>, items: &Vec<vcd::ScopeItem>) -> HashMap<vcd::IdCode, usize> {
-    let mut map = HashMap::new();
+// `var_type` as written in `$var <type> ...`, including the SystemVerilog types
+fn new_stream(var_type: &str) -> ValueChangeStream {
+    match var_type {
+        "integer" | "int" | "shortint" | "longint" | "byte" | "time" => {
+            ValueChangeStream::Integer(ValueChangeStreamImpl::new())
+        }
+        "parameter"                       => { ValueChangeStream::Parameter(ValueChangeStreamImpl::new()) }
+        "event"                           => { ValueChangeStream::Event    (ValueChangeStreamImpl::new()) }
+        "real" | "realtime" | "shortreal" => { ValueChangeStream::Real     (ValueChangeStreamImpl::new()) }
+        "string"                          => { ValueChangeStream::String   (ValueChangeStreamImpl::new()) }
+        _ => {
+            // wire, reg, tri, wand, wor, supply0/1, logic, bit, enum, ...
+            ValueChangeStream::Bits(ValueChangeStreamImpl::new())
+        }
+    }
+}
+
+// `kinds` are the scope kinds in the order of `$scope`, and `types` the original types
+// of variables by id code (see HeaderRewriter)
+pub fn append_to_scope(scope: &mut Scope, values: &mut Vec<ValueChangeStream>,
+                       map: &mut HashMap<vcd::IdCode, (usize, usize)>, items: &[vcd::ScopeItem],
+                       kinds: &mut impl Iterator<Item = ScopeKind>, types: &HashMap<String, String>) {
 
     for item in items.iter() {
         match item {
             vcd::ScopeItem::Var(v) => {
-                let idx = values.len(); // save current idx before push
-
-                match v.var_type {
-                    vcd::VarType::Wire   => { values.push(ValueChangeStream::Bits(ValueChangeStreamImpl::new()))},
-                    vcd::VarType::Reg    => { values.push(ValueChangeStream::Bits(ValueChangeStreamImpl::new()))},
-                    vcd::VarType::Real   => { values.push(ValueChangeStream::Real(ValueChangeStreamImpl::new()))},
-                    vcd::VarType::String => { values.push(ValueChangeStream::String(ValueChangeStreamImpl::new()))},
-                    _ => {
-                        // dump(format!("unsupported type {:?} found", v.var_type));
-                        values.push(ValueChangeStream::Unknown)
-                    }
-                };
-                map.insert(v.code, idx);
-                scope.items.push(ScopeItem::Value(ScopeValue::new(&v.reference, idx)));
+                // connected nets share the same id code. they also share the stream
+                let (idx, _) = *map.entry(v.code).or_insert_with(|| {
+                    let var_type = types.get(&v.code.to_string()).cloned().unwrap_or_else(|| v.var_type.to_string());
+                    values.push(new_stream(&var_type));
+                    (values.len() - 1, v.size as usize) // (index, declared width)
+                });
+                let mut value = ScopeValue::new(&v.reference, idx, v.size as usize);
+                value.range = v.index.map(|i| match i {
+                    vcd::ReferenceIndex::BitSelect(b)        => { BitRange::Bit(b) }
+                    vcd::ReferenceIndex::Range(left, right) => { BitRange::Range(left, right) }
+                });
+                scope.add_value(value);
             }
             _ => {
                 // do later
@@ -35,12 +53,12 @@ pub fn append_to_scope(scope: &mut Scope, values: &mut Vec<ValueChangeStream>, i
     for item in items.iter() {
         match item {
             vcd::ScopeItem::Scope(s) => {
-                let mut subscope = Scope::new(&s.identifier);
+                let kind = kinds.next().unwrap_or_else(|| ScopeKind::from_vcd_name(&s.scope_type.to_string()));
+                let mut subscope = Scope::new(&s.identifier, kind);
 
-                let submap = append_to_scope(&mut subscope, values, &s.items);
-                map.extend(submap);
+                append_to_scope(&mut subscope, values, map, &s.items, kinds, types);
 
-                scope.items.push(ScopeItem::Scope(subscope));
+                scope.add_scope(subscope);
             }
             vcd::ScopeItem::Var(_) => {
                 // already did
@@ -50,115 +68,730 @@ pub fn append_to_scope(scope: &mut Scope, values: &mut Vec<ValueChangeStream>, i
             }
         }
     }
+}
 
-    scope.items.sort_by(|lhs, rhs| {
-        match lhs {
-            ScopeItem::Scope(s1) => {
-                if let ScopeItem::Scope(s2) = rhs {
-                    s1.name.cmp(&s2.name)
-                } else {
-                    std::cmp::Ordering::Greater // value < scope
+pub fn make_value_tree(header: &vcd::Header, kinds: &[ScopeKind], types: &HashMap<String, String>)
+    -> (TimeSeries, HashMap<vcd::IdCode, (usize, usize)>)
+{
+    let mut ts = TimeSeries::new();
+    let mut map = HashMap::new();
+    append_to_scope(&mut ts.scope, Arc::make_mut(&mut ts.values), &mut map, &header.items, &mut kinds.iter().copied(), types);
+    (ts, map)
+}
+
+// removes values not matching `opts.signals` and scopes that become empty.
+// the indices of the streams still referred to are collected into `selected`.
+fn select_signals(scope: &mut Scope, path: &str, opts: &LoadOptions, selected: &mut HashSet<usize>) {
+    scope.items.retain_mut(|item| {
+        match item {
+            ScopeItem::Value(v) => {
+                let keep = opts.selects(&format!("{}{}", path, v.name));
+                if keep {
+                    selected.insert(v.index);
                 }
+                keep
             }
-            ScopeItem::Value(v1) => {
-                if let ScopeItem::Value(v2) = rhs {
-                    v1.name.cmp(&v2.name)
-                } else {
-                    std::cmp::Ordering::Less
-                }
+            ScopeItem::Scope(s) => {
+                select_signals(s, &format!("{}{}.", path, s.name), opts, selected);
+                !s.items.is_empty()
             }
         }
     });
-    map
 }
 
-pub fn make_value_tree(header: &vcd::Header) -> (TimeSeries, HashMap<vcd::IdCode, usize>)  {
-    let mut ts = TimeSeries::new();
-    let map = append_to_scope(&mut ts.scope, &mut ts.values, &header.items);
-    (ts, map)
+// $dumpall, $dumpvars and $dumpon list the current values of all the variables.
+// they are not changes, so skip values that are the same as the last one.
+fn push_value<T>(xs: &mut ValueChangeStreamImpl<T>, t: u64, v: T, checkpoint: bool)
+    where T: StreamValue
+{
+    if checkpoint && xs.last().is_some_and(|last| last.new_value == v) {
+        return;
+    }
+    xs.push_change(t, v);
 }
 
-pub fn load_vcd<R: std::io::BufRead>(src: R) -> anyhow::Result<TimeSeries> {
+// position in the VCD file, for error messages
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Location {
+    pub line: u64,
+    pub offset: u64, // in bytes
+}
 
-    let mut parser = vcd::Parser::new(src);
+// rewrites the scope kinds and variable types in the header that the vcd crate does not know.
+// it only knows module, task, function, begin and fork, so the other scope kinds (interface,
+// struct, vhdl_architecture, ...) are replaced by `begin` padded with spaces, so that line
+// numbers and byte offsets do not change (a kind shorter than `begin` makes the line longer,
+// see `inserted`). the original kinds are recorded in the order of `$scope`s.
+//
+// likewise the variable types it does not know (realtime, logic, bit, int, ...) are replaced
+// by `real` or `reg`, and the original types are recorded by id code. text in `$comment`,
+// `$date` and `$version` is left as is.
+pub struct HeaderRewriter<R> {
+    inner: R,
+    line: Vec<u8>, // the current line in the header
+    pos: usize,
+    in_header: bool,
+    in_text: bool, // in `$comment`, `$date` or `$version`
+    after_scope: bool, // the last token was `$scope`
+    var_field: usize, // tokens since `$var`: 1 for the type, 2 the size, 3 the id code
+    var_type: Option<String>, // replaced type of the current `$var`
+    line_no: u64,
+    inserted: u64, // bytes added to the header by replacing a token with a longer one
+    pub kinds: Vec<ScopeKind>,
+    pub var_types: HashMap<String, String>, // id code -> the original type
+    pub scope_lines: Vec<u64>, // line numbers of `$scope`s and `$var`s in the order of appearance
+    pub var_lines: Vec<u64>,
+}
 
-    let header = parser.parse_header()?;
-    let (mut ts, map) = make_value_tree(&header);
+impl<R: std::io::BufRead> HeaderRewriter<R> {
+    pub fn new(inner: R) -> Self {
+        Self{ inner, line: Vec::new(), pos: 0, in_header: true, in_text: false, after_scope: false, var_field: 0, var_type: None,
+              line_no: 0, inserted: 0, kinds: Vec::new(), var_types: HashMap::new(),
+              scope_lines: Vec::new(), var_lines: Vec::new() }
+    }
 
-    if let Some((coef, unit)) = header.timescale {
-        ts.time_scale.0 = coef;
-        ts.time_scale.1 = unit.to_string();
+    // replaces line[begin..end] with `name` padded with spaces. returns the end of the new token
+    fn replace_token(&mut self, begin: usize, end: usize, name: &str) -> usize {
+        let replaced = format!("{:<width$}", name, width = end - begin);
+        self.line.splice(begin..end, replaced.bytes());
+        self.inserted += (replaced.len() - (end - begin)) as u64;
+        begin + replaced.len()
+    }
+
+    fn read_header_line(&mut self) -> std::io::Result<()> {
+        self.line.clear();
+        self.pos = 0;
+        self.inner.read_until(b'\n', &mut self.line)?;
+        self.line_no += 1;
+
+        let mut i = 0;
+        while i < self.line.len() {
+            if self.line[i].is_ascii_whitespace() {
+                i += 1;
+                continue;
+            }
+            let begin = i;
+            while i < self.line.len() && !self.line[i].is_ascii_whitespace() {
+                i += 1;
+            }
+            let token = String::from_utf8_lossy(&self.line[begin..i]).to_string();
+            if self.in_text {
+                self.in_text = token != "$end";
+                continue;
+            }
+            if matches!(token.as_str(), "$comment" | "$date" | "$version") {
+                self.in_text = true;
+                continue;
+            }
+            if self.after_scope {
+                self.kinds.push(ScopeKind::from_vcd_name(&token));
+                if token.parse::<vcd::ScopeType>().is_err() {
+                    i = self.replace_token(begin, i, "begin");
+                }
+            }
+            match self.var_field {
+                1 if token.parse::<vcd::VarType>().is_err() => {
+                    let known = if matches!(token.as_str(), "realtime" | "shortreal") { "real" } else { "reg" };
+                    i = self.replace_token(begin, i, known);
+                    self.var_type = Some(token.clone());
+                }
+                3 => {
+                    if let Some(var_type) = self.var_type.take() {
+                        self.var_types.insert(token.clone(), var_type);
+                    }
+                }
+                _ => {}
+            }
+            self.var_field = if token == "$var" { 1 } else if 0 < self.var_field { self.var_field + 1 } else { 0 };
+            self.after_scope = token == "$scope";
+            if token == "$scope" {
+                self.scope_lines.push(self.line_no);
+            } else if token == "$var" {
+                self.var_lines.push(self.line_no);
+            } else if token == "$end" {
+                self.var_field = 0;
+            }
+            if token == "$enddefinitions" {
+                self.in_header = false;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<R: std::io::BufRead> std::io::Read for HeaderRewriter<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        use std::io::BufRead;
+        let src = self.fill_buf()?;
+        let n = src.len().min(buf.len());
+        buf[..n].copy_from_slice(&src[..n]);
+        self.consume(n);
+        Ok(n)
+    }
+}
+
+impl<R: std::io::BufRead> std::io::BufRead for HeaderRewriter<R> {
+    fn fill_buf(&mut self) -> std::io::Result<&[u8]> {
+        if self.line.len() <= self.pos && self.in_header {
+            self.read_header_line()?;
+        }
+        if self.pos < self.line.len() {
+            Ok(&self.line[self.pos..])
+        } else {
+            self.inner.fill_buf()
+        }
+    }
+    fn consume(&mut self, amt: usize) {
+        if self.pos < self.line.len() {
+            self.pos += amt;
+        } else {
+            self.inner.consume(amt);
+        }
+    }
+}
+
+// counts the bytes consumed by the parser, to locate errors
+pub struct CountingReader<R> {
+    inner: R,
+    offset: u64,
+}
+
+impl<R: std::io::BufRead> CountingReader<R> {
+    pub fn new(inner: R) -> Self {
+        Self{ inner, offset: 0 }
+    }
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+}
+
+impl<R: std::io::BufRead> std::io::Read for CountingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.offset += n as u64;
+        Ok(n)
+    }
+}
+
+impl<R: std::io::BufRead> std::io::BufRead for CountingReader<R> {
+    fn fill_buf(&mut self) -> std::io::Result<&[u8]> {
+        self.inner.fill_buf()
+    }
+    fn consume(&mut self, amt: usize) {
+        self.inner.consume(amt);
+        self.offset += amt as u64;
     }
+}
+
+pub type VcdParser<R> = vcd::Parser<CountingReader<HeaderRewriter<R>>>;
+
+pub fn new_parser<R: std::io::BufRead>(src: R) -> VcdParser<R> {
+    vcd::Parser::new(CountingReader::new(HeaderRewriter::new(src)))
+}
 
-    let mut current_t = 0;
+// for value changes after the header, e.g. from the middle of a file (see seek.rs)
+pub fn new_body_parser<R: std::io::BufRead>(src: R) -> VcdParser<R> {
+    let kinds = HeaderRewriter{ in_header: false, ..HeaderRewriter::new(src) };
+    vcd::Parser::new(CountingReader::new(kinds))
+}
 
-    for cmd in parser {
-        let cmd = cmd?;
-        match cmd {
+pub fn location<R: std::io::BufRead>(parser: &mut VcdParser<R>) -> Location {
+    // in the file, not counting what HeaderRewriter inserted
+    let line = parser.line();
+    let reader = parser.reader();
+    Location{ line, offset: reader.offset.saturating_sub(reader.get_ref().inserted) }
+}
+
+// the parser ends its messages with " at line N", which the location already tells
+fn strip_line(message: &str) -> &str {
+    match message.rsplit_once(" at line ") {
+        Some((text, n)) if !n.is_empty() && n.bytes().all(|c| c.is_ascii_digit()) => { text }
+        _ => { message }
+    }
+}
+
+#[derive(Debug)]
+pub struct LoadError {
+    pub location: Location,
+    pub signal: Option<String>,
+    pub message: String,
+}
+
+impl std::fmt::Display for LoadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "line {} (byte {}): ", self.location.line, self.location.offset)?;
+        if let Some(signal) = &self.signal {
+            write!(f, "{}: ", signal)?;
+        }
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for LoadError {}
+
+fn collect_names(items: &[vcd::ScopeItem], path: &str, names: &mut HashMap<vcd::IdCode, String>) {
+    for item in items.iter() {
+        match item {
+            vcd::ScopeItem::Var(v) => {
+                names.entry(v.code).or_insert_with(|| format!("{}{}", path, v.reference));
+            }
+            vcd::ScopeItem::Scope(s) => {
+                collect_names(&s.items, &format!("{}{}.", path, s.identifier), names);
+            }
+            _ => {}
+        }
+    }
+}
+
+fn collect_comments(items: &[vcd::ScopeItem], comments: &mut Vec<String>) {
+    for item in items.iter() {
+        match item {
+            vcd::ScopeItem::Comment(c) => { comments.push(c.trim().to_string()); }
+            vcd::ScopeItem::Scope(s)   => { collect_comments(&s.items, comments); }
+            _ => {}
+        }
+    }
+}
+
+// applies value changes to the TimeSeries. separated from `load_vcd` so that
+// value changes can also be fed incrementally (e.g. in follow mode)
+// a broken file may have a problem in every record. only the first ones are kept as warnings,
+// and the rest are counted in a last line
+#[derive(Debug, Default)]
+pub struct Warnings {
+    n: usize,
+    summary: Option<usize>, // index of the line counting the rest
+}
+
+impl Warnings {
+    const MAX: usize = 100;
+
+    pub fn push(&mut self, ts: &mut TimeSeries, message: String) {
+        self.n += 1;
+        if self.n <= Self::MAX {
+            ts.warnings.push(message);
+            return;
+        }
+        let more = format!("{} more warnings are not shown", self.n - Self::MAX);
+        match self.summary.and_then(|i| ts.warnings.get_mut(i)) {
+            Some(line) => { *line = more; }
+            None       => { self.summary = Some(ts.warnings.len()); ts.warnings.push(more); }
+        }
+    }
+}
+
+pub struct VcdLoader {
+    pub map: HashMap<vcd::IdCode, (usize, usize)>, // id -> (index, declared width)
+    pub names: HashMap<vcd::IdCode, String>, // id -> full path, for error messages
+    pub current_t: u64,
+    pub section: Option<vcd::SimulationCommand>, // inside of $dumpvars ... $end, etc.
+    pub lenient: bool, // skip bad records instead of stopping
+    pub check: bool,   // also report what is accepted when viewing (see check.rs)
+    pub from: u64, // changes before `from` are folded into the value at `from`
+    pub to: u64,   // changes after `to` are not loaded
+    pub file_scale: TimeScale, // the last `$timescale`. timestamps are in its ticks
+    pub scale: u64, // ticks of the TimeSeries in a tick of `file_scale`
+    pub offset: u64, // start of the current segment, when a dump restarts from #0 after `$timescale`
+    restarted: bool, // the first timestamp after `$timescale` may restart from #0
+    warnings: Warnings,
+}
+
+impl VcdLoader {
+    pub fn new(map: HashMap<vcd::IdCode, (usize, usize)>, names: HashMap<vcd::IdCode, String>, lenient: bool) -> Self {
+        Self{ map, names, current_t: 0, section: None, lenient, check: false, from: 0, to: u64::MAX,
+              file_scale: TimeScale::new(1, TimeUnit::Tau), scale: 1, offset: 0, restarted: false,
+              warnings: Warnings::default() }
+    }
+
+    // `$timescale` in the middle of the file. all the times are kept in the finest tick so far,
+    // so the changes already loaded are moved onto it when the new one is finer
+    fn change_timescale(&mut self, ts: &mut TimeSeries, scale: TimeScale) -> Result<(), String> {
+        self.file_scale = scale;
+        self.restarted = true;
+        if ts.time_scale.unit == TimeUnit::Tau {
+            // no timescale in the header. the ticks so far are taken as the new ones
+            ts.time_scale = scale;
+            return Ok(());
+        }
+        let common = ts.time_scale.common(&scale)
+            .ok_or_else(|| format!("timescale {} cannot be combined with {}", scale, ts.time_scale))?;
+        let factor = ts.time_scale.ticks_of(&common).unwrap_or(1);
+        if 1 < factor {
+            self.current_t = self.current_t.checked_mul(factor)
+                .ok_or_else(|| format!("#{} overflows in timescale {}", self.current_t, common))?;
+            self.offset = self.offset.saturating_mul(factor); // not more than `current_t`
+            self.from = self.from.saturating_mul(factor);
+            self.to   = self.to  .saturating_mul(factor);
+            ts.scale_time(common, factor);
+        }
+        self.scale = scale.ticks_of(&common).unwrap_or(1);
+        Ok(())
+    }
+
+    // the rest of the file does not need to be parsed
+    pub fn is_past_window(&self) -> bool {
+        self.to < self.current_t
+    }
+
+    // in lenient mode, an error is recorded as a warning and the record is skipped.
+    // check.rs takes the warnings as they come, and reports all of them
+    fn error(&mut self, ts: &mut TimeSeries, at: Location, code: Option<vcd::IdCode>, message: String) -> Result<(), LoadError> {
+        let signal = code.map(|c| self.names.get(&c).cloned().unwrap_or_else(|| c.to_string()));
+        let err = LoadError{ location: at, signal, message };
+        if self.check {
+            ts.warnings.push(err.to_string());
+            Ok(())
+        } else if self.lenient {
+            self.warnings.push(ts, err.to_string());
+            Ok(())
+        } else {
+            Err(err)
+        }
+    }
+
+    pub fn apply(&mut self, ts: &mut TimeSeries, cmd: std::io::Result<vcd::Command>, at: Location) -> Result<(), LoadError> {
+        let cmd = match cmd {
+            Ok(cmd) => { cmd }
+            Err(e)  => { return self.error(ts, at, None, strip_line(&e.to_string()).to_string()); }
+        };
+        let checkpoint = self.section.is_some();
+
+        let code = match &cmd {
+            vcd::Command::ChangeScalar(i, _) | vcd::Command::ChangeVector(i, _) |
+            vcd::Command::ChangeReal(i, _)   | vcd::Command::ChangeString(i, _) => { Some(*i) }
+            _ => { None }
+        };
+        let (idx, width) = match code {
+            Some(i) => {
+                match self.map.get(&i) {
+                    Some(x) => { *x }
+                    None if self.names.contains_key(&i) => { return Ok(()); } // not selected
+                    None    => { return self.error(ts, at, None, format!("undeclared id code {}", i)); }
+                }
+            }
+            None => { (0, 0) }
+        };
+        if code.is_some() && self.is_past_window() {
+            return Ok(());
+        }
+        // before the window, every change overwrites the value at `from`
+        let t = self.current_t.max(self.from);
+
+        let matched = match cmd {
             vcd::Command::Timestamp(t) => {
-                current_t = t;
-            }
-            vcd::Command::ChangeScalar(i, v) => {
-                let idx = map.get(&i).with_context(|| format!("ID {} NotFound", i))?;
-                // idxmut does not work here
-                if let ValueChangeStream::Bits(xs) = ts.values.get_mut(*idx).unwrap() {
-                    if let Some(last) = xs.stream.last() {
-                        if last.time == current_t {
-                            xs.stream.pop();
+                // concatenated dumps start again from #0. the new one follows the last time
+                let ticks = t.checked_mul(self.scale);
+                let restarted = std::mem::take(&mut self.restarted);
+                if restarted && ticks.is_some_and(|x| x.saturating_add(self.offset) < self.current_t) {
+                    self.offset = self.current_t;
+                }
+                let Some(t) = ticks.and_then(|x| x.checked_add(self.offset)) else {
+                    return self.error(ts, at, None, format!("#{} overflows in timescale {}", t, ts.time_scale));
+                };
+                if t < self.current_t {
+                    return self.error(ts, at, None, format!("timestamp goes back from {} to {}",
+                                                            ts.time_scale.format(self.current_t), ts.time_scale.format(t)));
+                }
+                self.current_t = t;
+                true
+            }
+            vcd::Command::Begin(c) => {
+                match c {
+                    vcd::SimulationCommand::Dumpoff if ts.dump_off.last().is_none_or(|(_, to)| *to != u64::MAX) => {
+                        ts.dump_off.push((t, u64::MAX));
+                    }
+                    vcd::SimulationCommand::Dumpon => {
+                        if let Some((_, to)) = ts.dump_off.last_mut().filter(|(_, to)| *to == u64::MAX) {
+                            *to = t;
                         }
                     }
-                    xs.stream.push(ValueChange::new(current_t, Bits::from_vcd_scalar(v)));
-                } else {
-                    panic!("type error");
+                    _ => {}
                 }
+                self.section = Some(c);
+                true
+            }
+            vcd::Command::End(_) => {
+                self.section = None;
+                true
+            }
+            vcd::Command::ChangeScalar(..) | vcd::Command::ChangeVector(..) |
+            vcd::Command::ChangeReal(..)   | vcd::Command::ChangeString(..)
+                if self.section == Some(vcd::SimulationCommand::Dumpoff) => {
+                // $dumpoff lists all the variables as X. the interval is shown as not dumped.
+                true
+            }
+            vcd::Command::ChangeVector(_, v) if self.check && width < v.len() => {
+                return self.error(ts, at, code, format!("{} bits for a {}-bit variable", v.len(), width));
             }
-            vcd::Command::ChangeVector(i, v) => {
-                let idx = map.get(&i).with_context(|| format!("ID {} NotFound", i))?;
-                if let ValueChangeStream::Bits(xs) = ts.values.get_mut(*idx).unwrap() {
-                    if let Some(last) = xs.stream.last() {
-                        if last.time == current_t {
-                            xs.stream.pop();
+            vcd::Command::ChangeScalar(..) if self.check && 1 < width &&
+                matches!(ts.values[idx], ValueChangeStream::Bits(_) | ValueChangeStream::Integer(_)) => {
+                return self.error(ts, at, code, format!("scalar value for a {}-bit variable", width));
+            }
+            vcd::Command::ChangeScalar(_, v) => {
+                match &mut ts.values_mut()[idx] {
+                    ValueChangeStream::Bits(xs) | ValueChangeStream::Integer(xs) | ValueChangeStream::Parameter(xs) => {
+                        push_value(xs, t, Bits::from_vcd_scalar(v), checkpoint);
+                        true
+                    }
+                    ValueChangeStream::Event(xs) => {
+                        if v == vcd::Value::V1 && self.from <= self.current_t {
+                            xs.push_change(t, ());
                         }
+                        true
                     }
-                    xs.stream.push(ValueChange::new(current_t, Bits::from_vcd_vector(v)));
-                } else {
-                    panic!("type error");
+                    _ => { false }
                 }
             }
-            vcd::Command::ChangeReal(i, v) => {
-                let idx = map.get(&i).with_context(|| format!("ID {} NotFound", i))?;
-                if let ValueChangeStream::Real(xs) = ts.values.get_mut(*idx).unwrap() {
-                    if let Some(last) = xs.stream.last() {
-                        if last.time == current_t {
-                            xs.stream.pop();
-                        }
+            vcd::Command::ChangeVector(_, v) => {
+                match &mut ts.values_mut()[idx] {
+                    ValueChangeStream::Bits(xs) | ValueChangeStream::Integer(xs) | ValueChangeStream::Parameter(xs) => {
+                        // VCD omits leading zeros. extend it to the declared width
+                        push_value(xs, t, Bits::from_vcd_vector(v).extend_to(width), checkpoint);
+                        true
                     }
-                    xs.stream.push(ValueChange::new(current_t, v));
+                    _ => { false }
+                }
+            }
+            vcd::Command::ChangeReal(_, v) => {
+                if let ValueChangeStream::Real(xs) = &mut ts.values_mut()[idx] {
+                    push_value(xs, t, v, checkpoint);
+                    true
                 } else {
-                    panic!("type error");
+                    false
                 }
             }
-            vcd::Command::ChangeString(i, v) => {
-                let idx = map.get(&i).with_context(|| format!("ID {} NotFound", i))?;
-                if let ValueChangeStream::String(xs) = ts.values.get_mut(*idx).unwrap() {
-                    if let Some(last) = xs.stream.last() {
-                        if last.time == current_t {
-                            xs.stream.pop();
-                        }
-                    }
-                    xs.stream.push(ValueChange::new(current_t, v));
+            vcd::Command::ChangeString(_, v) => {
+                if let ValueChangeStream::String(xs) = &mut ts.values_mut()[idx] {
+                    push_value(xs, t, v, checkpoint);
+                    true
                 } else {
-                    panic!("type error");
+                    false
                 }
             }
-            vcd::Command::Timescale(_,_) => {
-                panic!("varying timescale is not supported")
+            vcd::Command::Timescale(coef, unit) => {
+                let unit = TimeUnit::from_name(&unit.to_string()).unwrap_or(TimeUnit::Tau);
+                if let Err(message) = self.change_timescale(ts, TimeScale::new(coef, unit)) {
+                    return self.error(ts, at, None, message);
+                }
+                true
             }
             _ => {
                 // dump(format!("not supported command: {:?}", cmd));
+                true
             }
         };
+        if !matched {
+            return self.error(ts, at, code, "type error: value does not match the declared type".to_string());
+        }
+        Ok(())
+    }
+}
+
+pub fn parse_vcd_header<R: std::io::BufRead>(parser: &mut VcdParser<R>) -> anyhow::Result<vcd::Header> {
+    Ok(parser.parse_header().map_err(|e| {
+        LoadError{ location: location(parser), signal: None, message: e.to_string() }
+    })?)
+}
+
+pub fn load_vcd_header<R: std::io::BufRead>(parser: &mut VcdParser<R>, opts: &LoadOptions)
+    -> anyhow::Result<(TimeSeries, VcdLoader)>
+{
+    let header = parse_vcd_header(parser)?;
+    let reader = parser.reader().get_ref();
+    make_loader(&header, &reader.kinds, &reader.var_types, opts)
+}
+
+// `kinds` and `types` are what HeaderRewriter replaced in the header
+pub fn make_loader(header: &vcd::Header, kinds: &[ScopeKind], types: &HashMap<String, String>, opts: &LoadOptions)
+    -> anyhow::Result<(TimeSeries, VcdLoader)>
+{
+    let (mut ts, mut map) = make_value_tree(header, kinds, types);
+    if !opts.signals.is_empty() {
+        // streams of the other signals stay empty
+        let mut selected = HashSet::new();
+        select_signals(&mut ts.scope, "", opts, &mut selected);
+        if selected.is_empty() {
+            let patterns: Vec<&str> = opts.signals.iter().map(|re| re.as_str()).collect();
+            return Err(anyhow::anyhow!("no signal matches {}", patterns.join(", ")));
+        }
+        map.retain(|_, (idx, _)| selected.contains(idx));
+    }
+    bus::group_bit_blasted(&mut ts);
+
+    let mut names = HashMap::new();
+    collect_names(&header.items, "", &mut names);
+
+    if let Some((coef, unit)) = header.timescale {
+        let unit = TimeUnit::from_name(&unit.to_string()).unwrap_or(TimeUnit::Tau);
+        ts.time_scale = TimeScale::new(coef, unit);
+    }
+    ts.info.date    = header.date.as_ref().map(|s| s.trim().to_string());
+    ts.info.version = header.version.as_ref().map(|s| s.trim().to_string());
+    collect_comments(&header.items, &mut ts.info.comments);
+
+    let mut loader = VcdLoader::new(map, names, opts.lenient);
+    loader.file_scale = ts.time_scale;
+    (loader.from, loader.to) = opts.time_window(&ts.time_scale)?;
+    if 0 < loader.from {
+        ts.dump_off.push((0, loader.from)); // not loaded
+    }
+    Ok((ts, loader))
+}
+
+pub fn load_vcd<R: std::io::BufRead>(src: R, opts: &LoadOptions) -> anyhow::Result<TimeSeries> {
+
+    let mut parser = new_parser(src);
+
+    let (mut ts, mut loader) = load_vcd_header(&mut parser, opts)?;
+
+    while let Some(cmd) = parser.next() {
+        let at = location(&mut parser);
+        loader.apply(&mut ts, cmd, at)?;
+        if loader.is_past_window() {
+            break;
+        }
     }
+    bus::update_buses(&mut ts);
     Ok(ts)
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn load(vcd: &str) -> TimeSeries {
+        load_vcd(vcd.as_bytes(), &LoadOptions::default()).unwrap()
+    }
+
+    fn stream<'a>(ts: &'a TimeSeries, name: &str) -> &'a ValueChangeStream {
+        let ScopeItem::Scope(top) = &ts.scope.items[0] else { panic!("no top scope") };
+        let v = top.items.iter().find_map(|item| match item {
+            ScopeItem::Value(v) if v.name == name => { Some(v) }
+            _ => { None }
+        });
+        &ts.values[v.expect("no such value").index]
+    }
+
+    #[test]
+    fn system_verilog_types() {
+        let ts = load("$timescale 1ns $end
+$scope module top $end
+$var realtime 64 ! t $end
+$var logic 4 \" l $end
+$var bit 1 # b $end
+$var int 32 $ i $end
+$var shortreal 32 % f $end
+$var wire 1 & w $end
+$upscope $end
+$enddefinitions $end
+#0
+r1.5 !
+b1x0z \"
+1#
+b11111111111111111111111111111110 $
+r-0.25 %
+0&
+");
+        let ValueChangeStream::Real(t) = stream(&ts, "t") else { panic!("realtime is not real") };
+        assert_eq!(t.value(0), 1.5);
+        let ValueChangeStream::Bits(l) = stream(&ts, "l") else { panic!("logic is not bits") };
+        assert_eq!(l.value(0).to_radix_string(Radix::Bin), "1X0Z");
+        assert!(matches!(stream(&ts, "b"), ValueChangeStream::Bits(_)));
+        let ValueChangeStream::Integer(i) = stream(&ts, "i") else { panic!("int is not integer") };
+        assert_eq!(i.value(0).to_radix_string(Radix::Dec), "4294967294");
+        assert!(matches!(stream(&ts, "f"), ValueChangeStream::Real(_)));
+        assert!(matches!(stream(&ts, "w"), ValueChangeStream::Bits(_)));
+    }
+
+    #[test]
+    fn scope_kinds_outside_comments() {
+        let vcd = |kind: &str| format!("$comment
+  a $scope interface in a comment $end
+$timescale 1ns $end
+$scope module top $end
+$scope {} sub $end
+$var wire 1 ! a $end
+$upscope $end
+$scope vhdl_architecture arch $end
+$var wire 1 \" b $end
+$upscope $end
+$upscope $end
+$enddefinitions $end
+#5
+1!
+#3
+0!
+", kind);
+        let ts = load_vcd(vcd("sv").as_bytes(), &LoadOptions{ lenient: true, ..LoadOptions::default() }).unwrap();
+        let ScopeItem::Scope(top) = &ts.scope.items[0] else { panic!("no top scope") };
+        let kinds: Vec<(String, ScopeKind)> = top.items.iter().filter_map(|item| match item {
+            ScopeItem::Scope(s) => { Some((s.name.clone(), s.kind)) }
+            _ => { None }
+        }).collect();
+        assert_eq!(top.kind, ScopeKind::Module);
+        assert_eq!(kinds, vec![("sub".to_string(), ScopeKind::Other), ("arch".to_string(), ScopeKind::Module)]);
+
+        // a short kind is replaced by a longer one, but the location is in the file as it is
+        let error = |kind: &str| load_vcd(vcd(kind).as_bytes(), &LoadOptions::default()).unwrap_err().to_string();
+        for kind in ["sv", "task"] {
+            let after = vcd(kind).find("#3\n").unwrap() + 3;
+            assert!(error(kind).contains(&format!("line 15 (byte {})", after)), "{}", error(kind));
+        }
+    }
+
+    #[test]
+    fn concatenated_dumps() {
+        let vcd = "$timescale 10ps $end
+$scope module top $end
+$var wire 1 ! a $end
+$upscope $end
+$enddefinitions $end
+#0
+0!
+#30
+1!
+$timescale 1ns $end
+#0
+0!
+#2
+1!
+";
+        let ts = load(vcd);
+        assert_eq!(ts.time_scale, TimeScale::new(10, TimeUnit::Ps));
+        let ValueChangeStream::Bits(a) = stream(&ts, "a") else { panic!("not bits") };
+        let changes: Vec<(u64, Bits)> = a.iter_from(0).map(|c| (c.time, c.new_value)).collect();
+        // the second dump starts at 300ps, where its first value replaces the last one
+        assert_eq!(changes, vec![(0, Bits::B(false)), (30, Bits::B(false)), (230, Bits::B(true))]);
+
+        // not restarted, continues in the new timescale
+        let ts = load(&vcd.replace("#0\n0!\n#2", "#1\n0!\n#2"));
+        let ValueChangeStream::Bits(a) = stream(&ts, "a") else { panic!("not bits") };
+        assert_eq!(a.iter_from(0).map(|c| c.time).collect::<Vec<_>>(), vec![0, 30, 100, 200]);
+
+        // only right after $timescale
+        let err = load_vcd(format!("{}#1\n", vcd).as_bytes(), &LoadOptions::default()).unwrap_err();
+        assert!(err.to_string().contains("timestamp goes back"), "{}", err);
+    }
+
+    #[test]
+    fn unmatched_signal_pattern() {
+        let vcd = "$scope module top $end\n$var wire 1 ! a $end\n$upscope $end\n$enddefinitions $end\n#0\n1!\n";
+        let opts = LoadOptions{ signals: vec![LoadOptions::glob("top.b*").unwrap()], ..LoadOptions::default() };
+        let err = load_vcd(vcd.as_bytes(), &opts).unwrap_err();
+        assert_eq!(err.to_string(), "no signal matches ^top\\.b.*$");
+        let opts = LoadOptions{ signals: vec![LoadOptions::glob("top.a").unwrap()], ..LoadOptions::default() };
+        assert_eq!(load_vcd(vcd.as_bytes(), &opts).unwrap().values.len(), 1);
+    }
+
+    #[test]
+    fn warnings_are_capped() {
+        let mut vcd = "$scope module top $end\n$var wire 1 ! a $end\n$upscope $end\n$enddefinitions $end\n".to_string();
+        for t in 0..250 {
+            vcd += &format!("#{}\n1?\n", t);
+        }
+        let ts = load_vcd(vcd.as_bytes(), &LoadOptions{ lenient: true, ..LoadOptions::default() }).unwrap();
+        assert_eq!(ts.warnings.len(), 101);
+        assert_eq!(ts.warnings[0], "line 6 (byte 85): undeclared id code ?");
+        assert_eq!(ts.warnings[100], "150 more warnings are not shown");
+    }
+}
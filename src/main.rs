@@ -1,10 +1,22 @@
+mod time;
 mod timeseries;
+mod columns;
+mod cache;
 mod load_vcd;
+mod load_evcd;
+mod load_fst;
+mod load_ghw;
+mod bus;
+mod follow;
+mod check;
+mod seek;
 mod app;
 mod ui;
 mod log;
 
 use app::TuiWave;
+use timeseries::{LoadOptions, TimeSeries};
+use follow::{Follower, Progress};
 
 use crossterm::ExecutableCommand;
 use crossterm::event::{
@@ -15,10 +27,8 @@ fn update(app: &mut TuiWave) -> anyhow::Result<()> {
 
     if crossterm::event::poll(std::time::Duration::from_millis(1000/60))? {
         match crossterm::event::read()? {
-            Event::Key(key) => {
-                if key.kind == KeyEventKind::Press {
-                    app.key_press(key.code, key.modifiers, key.state);
-                }
+            Event::Key(key) if key.kind == KeyEventKind::Press => {
+                app.key_press(key.code, key.modifiers, key.state);
             },
             Event::Resize(w, h) => {
                 app.resize(w, h);
@@ -29,6 +39,197 @@ fn update(app: &mut TuiWave) -> anyhow::Result<()> {
     Ok(())
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Compression {
+    Gzip,
+    Zstd,
+    Xz,
+}
+
+fn detect_compression<R: std::io::BufRead>(fname: &str, reader: &mut R) -> anyhow::Result<Option<Compression>> {
+    let magic = reader.fill_buf()?;
+    if fname.ends_with(".gz") || magic.starts_with(&[0x1f, 0x8b]) {
+        Ok(Some(Compression::Gzip))
+    } else if fname.ends_with(".zst") || magic.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) {
+        Ok(Some(Compression::Zstd))
+    } else if fname.ends_with(".xz") || magic.starts_with(&[0xfd, 0x37, 0x7a, 0x58, 0x5a, 0x00]) {
+        Ok(Some(Compression::Xz))
+    } else {
+        Ok(None)
+    }
+}
+
+// decompress on the fly, so that we don't need to unpack it onto the disk
+fn decompress<R: std::io::BufRead + Send + 'static>(compression: Compression, reader: R)
+    -> anyhow::Result<Box<dyn std::io::BufRead + Send>>
+{
+    use std::io::BufReader;
+    Ok(match compression {
+        Compression::Gzip => { Box::new(BufReader::new(flate2::bufread::MultiGzDecoder::new(reader))) }
+        Compression::Zstd => { Box::new(BufReader::new(zstd::stream::read::Decoder::with_buffer(reader)?)) }
+        Compression::Xz   => { Box::new(BufReader::new(xz2::bufread::XzDecoder::new_multi_decoder(reader))) }
+    })
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Format {
+    Vcd(Option<Compression>),
+    Evcd(Option<Compression>),
+    Fst,
+    Ghw,
+}
+
+// the header is sniffed once, and the format is passed along
+fn detect_format(fname: &str) -> anyhow::Result<Format> {
+    use std::io::Seek;
+    let f = std::fs::File::open(fname)?;
+    let mut reader = std::io::BufReader::new(f);
+
+    if let Some(compression) = detect_compression(fname, &mut reader)? {
+        // only VCD can be read from a stream. FST and GHW need random access.
+        return Ok(match load_evcd::is_evcd(decompress(compression, reader)?)? {
+            true  => { Format::Evcd(Some(compression)) }
+            false => { Format::Vcd(Some(compression)) }
+        });
+    }
+    if fname.ends_with(".evcd") || load_evcd::is_evcd(&mut reader)? {
+        return Ok(Format::Evcd(None));
+    }
+    reader.rewind()?; // the header may not fit in the buffer
+    if fname.ends_with(".fst") || fst_reader::is_fst_file(&mut reader) {
+        Ok(Format::Fst)
+    } else if fname.ends_with(".ghw") || wellen::viewers::detect_file_format(&mut reader) == wellen::FileFormat::Ghw {
+        Ok(Format::Ghw)
+    } else {
+        Ok(Format::Vcd(None))
+    }
+}
+
+fn load_file(fname: &str, format: Format, opts: &LoadOptions) -> anyhow::Result<TimeSeries> {
+    let f = std::fs::File::open(fname)?;
+    let reader = std::io::BufReader::new(f);
+
+    match format {
+        Format::Vcd(Some(compression))  => { load_vcd::load_vcd(decompress(compression, reader)?, opts) }
+        Format::Vcd(None)               => { load_vcd::load_vcd(reader, opts) }
+        Format::Evcd(Some(compression)) => { load_evcd::load_evcd(decompress(compression, reader)?, opts) }
+        Format::Evcd(None)              => { load_evcd::load_evcd(reader, opts) }
+        Format::Fst                     => { load_fst::load_fst(reader, opts) }
+        Format::Ghw                     => { load_ghw::load_ghw(fname, opts) }
+    }
+}
+
+// read a file that is still being written, stdin, or a named pipe incrementally.
+// a VCD file is also parsed in the background, so that the viewer opens after its header.
+fn start_follow(fname: &str, format: Option<Format>, follow: bool, opts: &LoadOptions) -> anyhow::Result<Option<(TimeSeries, Follower, Option<Progress>)>> {
+    if fname == "-" {
+        let (ts, follower) = Follower::start(std::io::BufReader::new(std::io::stdin()), opts)?;
+        return Ok(Some((ts, follower, None)));
+    }
+    let f = std::fs::File::open(fname)?;
+    if !f.metadata()?.file_type().is_file() {
+        let (ts, follower) = Follower::start(std::io::BufReader::new(f), opts)?;
+        Ok(Some((ts, follower, None)))
+    } else if follow {
+        let (ts, follower) = Follower::start(std::io::BufReader::new(follow::TailReader::new(f)), opts)?;
+        Ok(Some((ts, follower, None)))
+    } else if let Some(Format::Vcd(compression)) = format {
+        let progress = Progress::new(f.metadata()?.len());
+        let reader = std::io::BufReader::new(progress.reader(f));
+        let (ts, follower) = match compression {
+            Some(compression) => { Follower::start(decompress(compression, reader)?, opts)? }
+            None              => { Follower::start(reader, opts)? }
+        };
+        Ok(Some((ts, follower, Some(progress))))
+    } else {
+        Ok(None)
+    }
+}
+
+// `tuiwave check file.vcd`. returns the number of problems found
+fn check_file(fname: &str) -> anyhow::Result<usize> {
+    let f = std::fs::File::open(fname)?;
+    let mut reader = std::io::BufReader::new(f);
+    let mut out = std::io::stdout().lock();
+    match detect_format(fname)? {
+        Format::Vcd(Some(compression)) => { check::check_vcd(decompress(compression, reader)?, &mut out) }
+        Format::Vcd(None)              => { check::check_vcd(&mut reader, &mut out) }
+        _                              => { Err(anyhow::anyhow!("only VCD files can be checked")) }
+    }
+}
+
+struct Options {
+    fname: String,
+    follow: bool,
+    cache: bool,
+    index: bool,
+    load: LoadOptions,
+}
+
+// a parsed VCD file is cached, so that it opens instantly next time.
+// the other formats are fast enough to load, and a partial load is not cached.
+fn cache_key(opts: &Options, format: Option<Format>) -> Option<cache::Key> {
+    if !opts.cache || opts.follow || opts.index || opts.load.is_filtered() || !matches!(format, Some(Format::Vcd(_))) {
+        return None;
+    }
+    cache::Key::new(&opts.fname, &opts.load).ok()
+}
+
+// for a VCD file too large to load, only the changes around the visible range are loaded
+fn start_pager(opts: &Options, format: Option<Format>) -> anyhow::Result<(TimeSeries, seek::Pager)> {
+    if opts.follow || opts.load.is_filtered() {
+        return Err(anyhow::anyhow!("--index cannot be used with --follow, --signal, --from or --to"));
+    }
+    if format != Some(Format::Vcd(None)) {
+        return Err(anyhow::anyhow!("--index needs an uncompressed VCD file"));
+    }
+    let size = std::fs::metadata(&opts.fname)?.len();
+    let mut reported = false;
+    let index = seek::SeekIndex::open_or_build(&opts.fname, &opts.load, &mut |read| {
+        eprint!("\rindexing {} / {} MB", read >> 20, size >> 20);
+        reported = true;
+    })?;
+    if reported {
+        eprintln!();
+    }
+    seek::Pager::new(&opts.fname, &opts.load, index)
+}
+
+fn parse_args() -> anyhow::Result<Option<Options>> {
+    let mut fname = None;
+    let mut follow = false;
+    let mut cache = true;
+    let mut index = false;
+    let mut load = LoadOptions::default();
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "--follow" || arg == "-f" {
+            follow = true;
+        } else if arg == "--index" {
+            index = true;
+        } else if arg == "--no-cache" {
+            cache = false;
+        } else if arg == "--lenient" {
+            load.lenient = true;
+        } else if arg == "--signal" || arg == "--signal-regex" || arg == "--from" || arg == "--to" {
+            let Some(value) = args.next() else {
+                return Ok(None);
+            };
+            match arg.as_str() {
+                "--signal" => { load.signals.push(LoadOptions::glob(&value)?) }
+                "--from"   => { load.from = Some(value) }
+                "--to"     => { load.to   = Some(value) }
+                _          => { load.signals.push(regex::Regex::new(&value)?) }
+            }
+        } else if arg.starts_with("--") || fname.is_some() {
+            return Ok(None);
+        } else {
+            fname = Some(arg);
+        }
+    }
+    Ok(fname.map(|fname| Options{ fname, follow, cache, index, load }))
+}
+
 fn startup() -> anyhow::Result<()> {
     std::io::stdout().execute(crossterm::terminal::EnterAlternateScreen)?;
     crossterm::terminal::enable_raw_mode()?;
@@ -41,36 +242,119 @@ fn shutdown() -> anyhow::Result<()> {
     Ok(())
 }
 
-fn main() -> anyhow::Result<()> {
-    let args: Vec<String> = std::env::args().collect();
-    if args.len() != 2 {
-        println!("usage: ./tuiwave [filename.vcd]");
-        return Err(anyhow::anyhow!("missing file"));
-    }
-
-    startup()?;
+// restore the terminal also when it panics
+fn install_panic_hook() {
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        let _ = shutdown();
+        default_hook(info);
+    }));
+}
 
+fn run(app: &mut TuiWave, follower: &mut Option<Follower>, pager: &mut Option<seek::Pager>) -> anyhow::Result<()> {
     let mut terminal = ratatui::terminal::Terminal::new(
         ratatui::backend::CrosstermBackend::new(std::io::stdout()))?;
     terminal.clear()?;
 
-    let f = std::fs::File::open(&args[1])?;
-    let ts = load_vcd::load_vcd(std::io::BufReader::new(f))?;
-
-    let mut app = TuiWave::new(ts);
     app.setup_with_terminal_size(terminal.size()?);
 
     loop {
-        update(&mut app)?;
+        update(app)?;
 
-        terminal.draw(|frame| { ui::draw_ui(&app, frame) })?;
+        if let Some(follower) = follower {
+            if app.cancel_loading {
+                follower.cancel(&mut app.ts);
+            }
+            let scale = app.ts.time_scale;
+            if follower.poll(&mut app.ts)? {
+                if let Some(factor) = scale.ticks_of(&app.ts.time_scale).filter(|f| 1 < *f) {
+                    app.scale_time(factor);
+                }
+                app.update_time_range(follower.current_time());
+            }
+            if follower.is_finished() {
+                app.loading = None;
+            }
+        }
+
+        if let Some(pager) = pager {
+            if pager.poll(&mut app.ts, app.t_from, app.t_to)? {
+                app.update_time_range(pager.end_time());
+            }
+        }
+
+        terminal.draw(|frame| { ui::draw_ui(app, frame) })?;
 
         if app.should_quit {
             break;
         }
     }
+    Ok(())
+}
+
+fn main() -> anyhow::Result<()> {
+    if std::env::args().nth(1).as_deref() == Some("check") {
+        let Some(fname) = std::env::args().nth(2) else {
+            println!("usage: ./tuiwave check filename.vcd");
+            return Err(anyhow::anyhow!("missing file"));
+        };
+        let n = check_file(&fname)?;
+        if n != 0 {
+            eprintln!("{}: {} problem(s) found", fname, n);
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
+    let Some(opts) = parse_args()? else {
+        println!("usage: ./tuiwave [--follow] [--lenient] [--no-cache] [--index] [--signal GLOB|--signal-regex REGEX]... [--from TIME] [--to TIME] \
+                  [filename.vcd|filename.evcd|filename.fst|filename.ghw|-]");
+        return Err(anyhow::anyhow!("missing file"));
+    };
 
+    // load the file (only the header of a VCD) before entering the alternate screen,
+    // so that errors are shown normally
+    // stdin and pipes cannot be read twice, and are taken as VCD
+    let is_file = opts.fname != "-" && std::fs::metadata(&opts.fname)?.is_file();
+    let format = if is_file { Some(detect_format(&opts.fname)?) } else { None };
+    let key = cache_key(&opts, format);
+    let mut follower = None;
+    let mut progress = None;
+    let mut pager = None;
+    let ts = if opts.index {
+        let (ts, p) = start_pager(&opts, format)?;
+        pager = Some(p);
+        ts
+    } else if let Some(ts) = key.as_ref().and_then(|key| cache::load(&opts.fname, key)) {
+        ts
+    } else if let Some((ts, mut f, p)) = start_follow(&opts.fname, format, opts.follow, &opts.load)? {
+        if let Some(key) = key {
+            f.write_cache(&opts.fname, key, &ts);
+        }
+        follower = Some(f);
+        progress = p;
+        ts
+    } else {
+        load_file(&opts.fname, format.unwrap_or(Format::Vcd(None)), &opts.load)?
+    };
+
+    let mut app = TuiWave::new(ts);
+    app.auto_scroll = follower.is_some() && progress.is_none();
+    app.loading = progress;
+    app.file_name = opts.fname.clone();
+    app.file_size = std::fs::metadata(&opts.fname).ok().map(|m| m.len());
+
+    install_panic_hook();
+    startup()?;
+    let result = run(&mut app, &mut follower, &mut pager);
     shutdown()?;
+    if let Some(follower) = &mut follower {
+        follower.stop(&mut app.ts);
+    }
+    result?;
 
-    return Ok(());
+    for warning in app.ts.warnings.iter() {
+        eprintln!("warning: {}", warning);
+    }
+    Ok(())
 }
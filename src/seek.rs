@@ -0,0 +1,361 @@
+use crate::timeseries::*;
+use crate::bus;
+use crate::cache::{self, CacheFile, Cached, MappedCache, Reader};
+use crate::load_vcd;
+
+use std::io::{BufReader, Seek, Write};
+use std::sync::{mpsc, Arc};
+
+// a sparse index into a VCD file too large to load at once. the first pass records the byte
+// offset of a timestamp every `interval` bytes with the values of all the signals at that point.
+// a time window is decoded later by seeking to the last point before it, so that the memory
+// depends on the window, not on the file. the index is kept next to the file, as the cache is.
+
+const EXT: &str = "tuiwave-index";
+
+// the value of a signal at an index point
+#[derive(Debug, Clone, PartialEq)]
+enum Sample {
+    None, // not dumped yet, or an event
+    Bits(Bits),
+    Real(f64),
+    String(String),
+}
+
+impl Sample {
+    // None if it has not changed since the last point
+    fn of(stream: &ValueChangeStream) -> Option<Self> {
+        match stream {
+            ValueChangeStream::Bits(xs) | ValueChangeStream::Integer(xs) |
+            ValueChangeStream::Parameter(xs) | ValueChangeStream::Enum(xs, _) => {
+                xs.last().map(|c| Sample::Bits(c.new_value))
+            }
+            ValueChangeStream::Real(xs)   => { xs.last().map(|c| Sample::Real(c.new_value)) }
+            ValueChangeStream::String(xs) => { xs.last().map(|c| Sample::String(c.new_value)) }
+            _ => { None }
+        }
+    }
+
+    fn push_to(&self, stream: &mut ValueChangeStream, t: u64) {
+        match (self, stream) {
+            (Sample::Bits(v), ValueChangeStream::Bits(xs)) | (Sample::Bits(v), ValueChangeStream::Integer(xs)) |
+            (Sample::Bits(v), ValueChangeStream::Parameter(xs)) | (Sample::Bits(v), ValueChangeStream::Enum(xs, _)) => {
+                xs.push_change(t, v.clone());
+            }
+            (Sample::Real(v),   ValueChangeStream::Real(xs))   => { xs.push_change(t, *v); }
+            (Sample::String(v), ValueChangeStream::String(xs)) => { xs.push_change(t, v.clone()); }
+            _ => {}
+        }
+    }
+}
+
+impl Cached for Sample {
+    fn write(&self, w: &mut dyn Write) -> std::io::Result<()> {
+        match self {
+            Sample::None      => { 0_u8.write(w) }
+            Sample::Bits(v)   => { 1_u8.write(w)?; v.write(w) }
+            Sample::Real(v)   => { 2_u8.write(w)?; v.write(w) }
+            Sample::String(v) => { 3_u8.write(w)?; v.write(w) }
+        }
+    }
+    fn read(r: &mut Reader<'_>) -> anyhow::Result<Self> {
+        match u8::read(r)? {
+            0   => { Ok(Sample::None) }
+            1   => { Ok(Sample::Bits(Bits::read(r)?)) }
+            2   => { Ok(Sample::Real(f64::read(r)?)) }
+            3   => { Ok(Sample::String(String::read(r)?)) }
+            tag => { Err(anyhow::anyhow!("broken index: unknown sample {}", tag)) }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct IndexPoint {
+    time: u64,    // the timestamp just before `offset`
+    offset: u64,  // in the VCD file
+    samples: u64, // offset of `Vec<Sample>` in the index file
+}
+
+impl Cached for IndexPoint {
+    fn write(&self, w: &mut dyn Write) -> std::io::Result<()> {
+        self.time.write(w)?;
+        self.offset.write(w)?;
+        self.samples.write(w)
+    }
+    fn read(r: &mut Reader<'_>) -> anyhow::Result<Self> {
+        Ok(Self{ time: u64::read(r)?, offset: u64::read(r)?, samples: u64::read(r)? })
+    }
+}
+
+// the file is [header][samples of each point][points][end time][dump off][warnings][offset of points]
+pub struct SeekIndex {
+    cache: MappedCache,
+    points: Vec<IndexPoint>,
+    end_time: u64,
+    dump_off: Vec<(u64, u64)>,
+    warnings: Vec<String>,
+}
+
+impl SeekIndex {
+    const MIN_INTERVAL: u64 = 16 << 20; // bytes
+    const MAX_POINTS: u64 = 4096;
+
+    fn open(fname: &str, key: &cache::Key) -> Option<Self> {
+        let cache = MappedCache::open(fname, EXT, key)?;
+        let table = u64::read(&mut cache.reader_at(cache.len().checked_sub(8)?)).ok()?;
+        let mut r = cache.reader_at(table as usize);
+        let points   = Vec::read(&mut r).ok()?;
+        let end_time = u64::read(&mut r).ok()?;
+        let dump_off = Vec::read(&mut r).ok()?;
+        let warnings = Vec::read(&mut r).ok()?;
+        Some(Self{ cache, points, end_time, dump_off, warnings })
+    }
+
+    // the first pass. a point is recorded every `interval` bytes.
+    // `report` is called with the number of bytes read at every point
+    fn build(fname: &str, opts: &LoadOptions, key: &cache::Key, interval: u64, report: &mut dyn FnMut(u64)) -> anyhow::Result<()> {
+        let mut f = CacheFile::create(fname, EXT, key)?;
+
+        let mut parser = load_vcd::new_parser(BufReader::new(std::fs::File::open(fname)?));
+        let (mut ts, mut loader) = load_vcd::load_vcd_header(&mut parser, opts)?;
+
+        let mut current = vec![Sample::None; ts.values.len()];
+        let offset = load_vcd::location(&mut parser).offset;
+        let mut points = vec![IndexPoint{ time: 0, offset, samples: f.position() }];
+        current.write(&mut f)?;
+        let mut next = offset + interval;
+
+        while let Some(cmd) = parser.next() {
+            let at = load_vcd::location(&mut parser);
+            let is_timestamp = matches!(cmd, Ok(vcd::Command::Timestamp(_)));
+            if let Ok(vcd::Command::Timescale(..)) = cmd {
+                // the points before it would have to be moved onto the new tick
+                return Err(anyhow::anyhow!("line {}: --index does not support $timescale after the header", at.line));
+            }
+            loader.apply(&mut ts, cmd, at)?;
+
+            // changes at the timestamp come after it, so the values are the ones just before it
+            if is_timestamp && loader.section.is_none() && next <= at.offset {
+                for (sample, stream) in current.iter_mut().zip(ts.values_mut().iter_mut()) {
+                    if let Some(s) = Sample::of(stream) {
+                        *sample = s;
+                    }
+                    stream.clear(); // to keep the memory bounded
+                }
+                points.push(IndexPoint{ time: loader.current_t, offset: at.offset, samples: f.position() });
+                current.write(&mut f)?;
+                next = at.offset + interval;
+                report(at.offset);
+            }
+        }
+        let table = f.position();
+        points.write(&mut f)?;
+        loader.current_t.write(&mut f)?;
+        ts.dump_off.write(&mut f)?;
+        ts.warnings.write(&mut f)?;
+        table.write(&mut f)?;
+        f.finish()
+    }
+
+    pub fn open_or_build(fname: &str, opts: &LoadOptions, report: &mut dyn FnMut(u64)) -> anyhow::Result<Self> {
+        let key = cache::Key::new(fname, opts)?;
+        if let Some(index) = Self::open(fname, &key) {
+            return Ok(index);
+        }
+        let size = std::fs::metadata(fname)?.len();
+        let interval = (size / Self::MAX_POINTS).max(Self::MIN_INTERVAL);
+        Self::build(fname, opts, &key, interval, report)?;
+        Self::open(fname, &key).ok_or_else(|| anyhow::anyhow!("failed to read the index of {}", fname))
+    }
+
+    pub fn end_time(&self) -> u64 {
+        self.end_time
+    }
+
+    // decodes changes until `to`, from the last point at or before `from`. it stops at a timestamp
+    // after `max_bytes` are read. the time range not loaded is marked as not dumped.
+    // returns the range actually loaded with the values
+    fn load_window(&self, fname: &str, opts: &LoadOptions, from: u64, to: u64, max_bytes: u64)
+        -> anyhow::Result<Window>
+    {
+        let mut parser = load_vcd::new_parser(BufReader::new(std::fs::File::open(fname)?));
+        let (mut ts, mut loader) = load_vcd::load_vcd_header(&mut parser, opts)?;
+
+        let point = &self.points[self.points.partition_point(|p| p.time <= from).saturating_sub(1)];
+        let samples: Vec<Sample> = Vec::read(&mut self.cache.reader_at(point.samples as usize))?;
+        for (sample, stream) in samples.iter().zip(ts.values_mut().iter_mut()) {
+            sample.push_to(stream, point.time);
+        }
+
+        let mut file = std::fs::File::open(fname)?;
+        file.seek(std::io::SeekFrom::Start(point.offset))?;
+        let mut parser = load_vcd::new_body_parser(BufReader::new(file));
+        loader.lenient = true; // problems are already reported by the first pass
+        loader.current_t = point.time;
+        loader.to = to;
+
+        let mut loaded_to = to;
+        while let Some(cmd) = parser.next() {
+            let at = load_vcd::location(&mut parser);
+            let is_timestamp = matches!(cmd, Ok(vcd::Command::Timestamp(_)));
+            loader.apply(&mut ts, cmd, at)?;
+            if loader.is_past_window() {
+                break;
+            }
+            if is_timestamp && loader.section.is_none() && max_bytes <= at.offset {
+                loaded_to = loader.current_t.saturating_sub(1);
+                break;
+            }
+        }
+        bus::update_buses(&mut ts);
+
+        ts.dump_off = self.dump_off.clone();
+        if 0 < point.time {
+            ts.dump_off.push((0, point.time));
+        }
+        if loaded_to < self.end_time {
+            ts.dump_off.push((loaded_to + 1, u64::MAX));
+        }
+        ts.warnings = self.warnings.clone();
+        Ok((ts, (point.time, loaded_to)))
+    }
+}
+
+// the values in a window and the time range loaded
+type Window = (TimeSeries, (u64, u64));
+
+// keeps the changes around the visible time range loaded. a window is decoded in a background
+// thread, and the values on the screen are kept until it is ready.
+pub struct Pager {
+    fname: String,
+    opts: LoadOptions,
+    index: Arc<SeekIndex>,
+    max_bytes: u64,  // per window
+    loaded: (u64, u64),             // time range actually loaded
+    requested: Option<(u64, u64)>,  // the last visible range a window was loaded for
+    receiver: Option<mpsc::Receiver<anyhow::Result<Window>>>, // while loading
+}
+
+impl Pager {
+    const MAX_BYTES: u64 = 256 << 20;
+
+    // starts with the values at t=0
+    pub fn new(fname: &str, opts: &LoadOptions, index: SeekIndex) -> anyhow::Result<(TimeSeries, Self)> {
+        let (ts, loaded) = index.load_window(fname, opts, 0, 0, Self::MAX_BYTES)?;
+        Ok((ts, Self{ fname: fname.to_string(), opts: opts.clone(), index: Arc::new(index), max_bytes: Self::MAX_BYTES,
+                      loaded, requested: None, receiver: None }))
+    }
+
+    pub fn end_time(&self) -> u64 {
+        self.index.end_time()
+    }
+
+    // starts loading the visible range with margins of its width on both sides if it is not
+    // loaded yet, and returns true when the values are replaced. the scope tree is kept as it is.
+    pub fn poll(&mut self, ts: &mut TimeSeries, t_from: u64, t_to: u64) -> anyhow::Result<bool> {
+        if let Some(receiver) = &self.receiver {
+            let (loaded, range) = match receiver.try_recv() {
+                Ok(result)                     => { result? }
+                Err(mpsc::TryRecvError::Empty) => { return Ok(false); }
+                Err(mpsc::TryRecvError::Disconnected) => {
+                    self.receiver = None;
+                    return Err(anyhow::anyhow!("failed to load a window of {}", self.fname));
+                }
+            };
+            self.receiver = None;
+            self.loaded = range;
+            ts.values   = loaded.values;
+            ts.dump_off = loaded.dump_off;
+            return Ok(true);
+        }
+
+        let t_to = t_to.min(self.index.end_time);
+        if self.loaded.0 <= t_from && t_to <= self.loaded.1 {
+            return Ok(false);
+        }
+        if self.requested == Some((t_from, t_to)) {
+            return Ok(false); // loaded as much as `max_bytes` allows
+        }
+        self.requested = Some((t_from, t_to));
+
+        let span = t_to.saturating_sub(t_from) + 1;
+        let (from, to) = (t_from.saturating_sub(span), t_to.saturating_add(span));
+        let (index, fname, opts, max_bytes) = (self.index.clone(), self.fname.clone(), self.opts.clone(), self.max_bytes);
+        let (sender, receiver) = mpsc::channel();
+        std::thread::spawn(move || {
+            let _ = sender.send(index.load_window(&fname, &opts, from, to, max_bytes));
+        });
+        self.receiver = Some(receiver);
+        Ok(false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn value_at(stream: &ValueChangeStream, t: u64) -> Option<Bits> {
+        let ValueChangeStream::Bits(xs) = stream else { panic!("not bits") };
+        xs.change_before(t).map(|i| xs.value(i))
+    }
+
+    // waits for the background thread
+    fn poll(pager: &mut Pager, ts: &mut TimeSeries, t_from: u64, t_to: u64) -> bool {
+        let started = pager.poll(ts, t_from, t_to).unwrap();
+        if started || pager.receiver.is_none() {
+            return started;
+        }
+        for _ in 0..1000 {
+            if pager.poll(ts, t_from, t_to).unwrap() {
+                return true;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+        panic!("the window is not loaded");
+    }
+
+    #[test]
+    fn pager_loads_the_visible_range() {
+        let dir = std::env::temp_dir().join(format!("tuiwave-seek-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let fname = dir.join("counter.vcd").to_string_lossy().to_string();
+        let mut src = String::from("$timescale 1ns $end\n$scope module top $end\n$var wire 1 ! clk $end\n\
+                                    $var wire 16 \" count $end\n$upscope $end\n$enddefinitions $end\n");
+        for t in 0..5000_u64 {
+            src += &format!("#{}\n{}!\nb{:b} \"\n", t * 10, t % 2, t * 7 % 65536);
+        }
+        std::fs::write(&fname, &src).unwrap();
+
+        let opts = LoadOptions::default();
+        let full = crate::load_vcd::load_vcd(src.as_bytes(), &opts).unwrap();
+        let key = cache::Key::new(&fname, &opts).unwrap();
+        SeekIndex::build(&fname, &opts, &key, 8192, &mut |_| {}).unwrap();
+        let index = SeekIndex::open(&fname, &key).unwrap();
+        assert!(10 < index.points.len());
+        let end = index.end_time();
+        assert_eq!(end, 49990);
+
+        let (mut ts, mut pager) = Pager::new(&fname, &opts, index).unwrap();
+        pager.max_bytes = 4096;
+
+        // zoomed out, only the beginning fits in `max_bytes`
+        assert!(poll(&mut pager, &mut ts, 0, end));
+        let (from, to) = pager.loaded;
+        assert!(from == 0 && to < end);
+        assert!(ts.dump_off.contains(&(to + 1, u64::MAX)));
+        for t in (0..=to).step_by(7) {
+            assert_eq!(value_at(&ts.values[1], t), value_at(&full.values[1], t), "t = {}", t);
+        }
+        assert!(!poll(&mut pager, &mut ts, 0, end)); // not loaded again
+
+        // zoomed in after the part loaded
+        assert!(poll(&mut pager, &mut ts, 40000, 40100));
+        assert!(pager.loaded.0 <= 40000 && 40100 <= pager.loaded.1);
+        for t in 40000..=40100 {
+            assert_eq!(value_at(&ts.values[1], t), value_at(&full.values[1], t), "t = {}", t);
+        }
+        assert!(!poll(&mut pager, &mut ts, 40010, 40090));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
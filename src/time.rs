@@ -0,0 +1,215 @@
+// physical time. value changes are recorded in ticks, and a tick is `coef * unit`.
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum TimeUnit {
+    Zs,
+    As,
+    Fs,
+    Ps,
+    Ns,
+    Us,
+    Ms,
+    S,
+    Tau, // no physical unit is given
+}
+
+impl TimeUnit {
+    const PHYSICAL: [TimeUnit; 8] = [
+        TimeUnit::Zs, TimeUnit::As, TimeUnit::Fs, TimeUnit::Ps,
+        TimeUnit::Ns, TimeUnit::Us, TimeUnit::Ms, TimeUnit::S,
+    ];
+
+    // 1 unit = 10^exponent s
+    pub fn exponent(self) -> Option<i32> {
+        match self {
+            TimeUnit::Zs  => { Some(-21) }
+            TimeUnit::As  => { Some(-18) }
+            TimeUnit::Fs  => { Some(-15) }
+            TimeUnit::Ps  => { Some(-12) }
+            TimeUnit::Ns  => { Some( -9) }
+            TimeUnit::Us  => { Some( -6) }
+            TimeUnit::Ms  => { Some( -3) }
+            TimeUnit::S   => { Some(  0) }
+            TimeUnit::Tau => { None }
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            TimeUnit::Zs  => { "zs" }
+            TimeUnit::As  => { "as" }
+            TimeUnit::Fs  => { "fs" }
+            TimeUnit::Ps  => { "ps" }
+            TimeUnit::Ns  => { "ns" }
+            TimeUnit::Us  => { "us" }
+            TimeUnit::Ms  => { "ms" }
+            TimeUnit::S   => { "s"  }
+            TimeUnit::Tau => { "tau" }
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "zs"        => { Some(TimeUnit::Zs) }
+            "as"        => { Some(TimeUnit::As) }
+            "fs"        => { Some(TimeUnit::Fs) }
+            "ps"        => { Some(TimeUnit::Ps) }
+            "ns"        => { Some(TimeUnit::Ns) }
+            "us" | "µs" => { Some(TimeUnit::Us) }
+            "ms"        => { Some(TimeUnit::Ms) }
+            "s"         => { Some(TimeUnit::S ) }
+            "tau"       => { Some(TimeUnit::Tau) }
+            _           => { None }
+        }
+    }
+
+    // 1 unit in zeptoseconds, the finest unit
+    fn zs(self) -> Option<u128> {
+        self.exponent().map(|e| 10_u128.pow((e + 21) as u32))
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TimeScale {
+    pub coef: u32,
+    pub unit: TimeUnit,
+}
+
+impl TimeScale {
+    pub fn new(coef: u32, unit: TimeUnit) -> Self {
+        Self{ coef, unit }
+    }
+
+    fn zs_per_tick(&self) -> Option<u128> {
+        self.unit.zs().map(|zs| zs * self.coef as u128)
+    }
+
+    // ticks -> "12.5 ns", using the largest unit that is not less than 1
+    pub fn format(&self, ticks: u64) -> String {
+        // in the declared unit if not physical, or too long to count in zeptoseconds
+        let Some(total) = self.zs_per_tick().and_then(|zs| (ticks as u128).checked_mul(zs)) else {
+            return format!("{} {}", ticks as u128 * self.coef as u128, self.unit.name());
+        };
+        let unit = TimeUnit::PHYSICAL.iter().rev()
+            .find(|u| u.zs().unwrap() <= total)
+            .copied()
+            .unwrap_or(self.unit);
+
+        let den = unit.zs().unwrap();
+        let (int, frac) = (total / den, total % den);
+        if frac == 0 {
+            return format!("{} {}", int, unit.name());
+        }
+        let digits = (unit.exponent().unwrap() + 21) as usize;
+        let frac = format!("{:0>width$}", frac, width = digits);
+        format!("{}.{} {}", int, frac.trim_end_matches('0'), unit.name())
+    }
+
+    // "12.5 ns", "3us", or "100" in ticks -> ticks, rounded to the nearest tick
+    pub fn parse(&self, s: &str) -> Option<u64> {
+        let s = s.trim();
+        let split = s.find(|c: char| !(c.is_ascii_digit() || c == '.')).unwrap_or(s.len());
+        let (num, unit) = (&s[..split], s[split..].trim());
+
+        let (int, frac) = num.split_once('.').unwrap_or((num, ""));
+        if int.is_empty() && frac.is_empty() {
+            return None;
+        }
+        let int: u128 = if int.is_empty() { 0 } else { int.parse().ok()? };
+        let frac_value: u128 = if frac.is_empty() { 0 } else { frac.parse().ok()? };
+        let frac_den = 10_u128.checked_pow(frac.len() as u32)?;
+
+        if unit.is_empty() {
+            // in ticks, which have no fraction
+            return if num.contains('.') { None } else { u64::try_from(int).ok() };
+        }
+        let unit_zs = TimeUnit::from_name(unit)?.zs()?;
+        let zs_per_tick = self.zs_per_tick()?;
+
+        let total = int.checked_mul(unit_zs)?.checked_add(frac_value.checked_mul(unit_zs)? / frac_den)?;
+        u64::try_from((total + zs_per_tick / 2) / zs_per_tick).ok()
+    }
+
+    // the longest tick that both ticks are multiples of, e.g. 10 ps for 1 ns and 10 ps
+    pub fn common(&self, other: &TimeScale) -> Option<TimeScale> {
+        let (mut a, mut b) = (self.zs_per_tick()?, other.zs_per_tick()?);
+        while b != 0 {
+            (a, b) = (b, a % b);
+        }
+        TimeUnit::PHYSICAL.iter().rev()
+            .filter(|u| a % u.zs().unwrap() == 0)
+            .find_map(|u| u32::try_from(a / u.zs().unwrap()).ok().map(|coef| TimeScale::new(coef, *u)))
+    }
+
+    // the number of `finer` ticks in a tick. None if it is not a multiple of them
+    pub fn ticks_of(&self, finer: &TimeScale) -> Option<u64> {
+        let (a, b) = (self.zs_per_tick()?, finer.zs_per_tick()?);
+        if a % b != 0 {
+            return None;
+        }
+        u64::try_from(a / b).ok()
+    }
+}
+
+impl std::fmt::Display for TimeScale {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", self.coef, self.unit.name())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn common_tick() {
+        let ns = TimeScale::new(1, TimeUnit::Ns);
+        let ps10 = TimeScale::new(10, TimeUnit::Ps);
+        assert_eq!(ns.common(&ps10), Some(ps10));
+        assert_eq!(ps10.common(&ns), Some(ps10));
+        assert_eq!(TimeScale::new(100, TimeUnit::Ns).common(&TimeScale::new(10, TimeUnit::Us)), Some(TimeScale::new(100, TimeUnit::Ns)));
+        assert_eq!(TimeScale::new(1, TimeUnit::Ns).common(&TimeScale::new(1, TimeUnit::Tau)), None);
+        assert_eq!(ns.ticks_of(&ps10), Some(100));
+        assert_eq!(ps10.ticks_of(&ns), None);
+        assert_eq!(ns.ticks_of(&ns), Some(1));
+    }
+
+    #[test]
+    fn format() {
+        let ps10 = TimeScale::new(10, TimeUnit::Ps);
+        assert_eq!(ps10.format(0), "0 ps");
+        assert_eq!(ps10.format(5), "50 ps");
+        assert_eq!(ps10.format(1250), "12.5 ns");
+        assert_eq!(ps10.format(100_000), "1 us");
+        assert_eq!(ps10.format(100_001), "1.00001 us");
+        assert_eq!(TimeScale::new(1, TimeUnit::S).format(90), "90 s");
+        assert_eq!(TimeScale::new(1, TimeUnit::Tau).format(42), "42 tau");
+        assert_eq!(TimeScale::new(1, TimeUnit::Fs).format(u64::MAX), "18446.744073709551615 s");
+        assert_eq!(TimeScale::new(100, TimeUnit::S).format(u64::MAX), "1844674407370955161500 s");
+    }
+
+    #[test]
+    fn parse() {
+        let ns = TimeScale::new(1, TimeUnit::Ns);
+        assert_eq!(ns.parse("12"), Some(12)); // in ticks
+        assert_eq!(ns.parse("12.5"), None);
+        assert_eq!(ns.parse("12."), None);
+        assert_eq!(ns.parse("3us"), Some(3000));
+        assert_eq!(ns.parse(" 12.5 ns "), Some(13)); // rounded
+        assert_eq!(ns.parse("12.4ns"), Some(12));
+        assert_eq!(ns.parse(".5us"), Some(500));
+        assert_eq!(ns.parse("1 µs"), Some(1000));
+        assert_eq!(ns.parse("1 ps"), Some(0));
+        assert_eq!(ns.parse(""), None);
+        assert_eq!(ns.parse("."), None);
+        assert_eq!(ns.parse("1 parsec"), None);
+        assert_eq!(ns.parse("1.2.3 ns"), None);
+        assert_eq!(ns.parse("100000000000 s"), None); // does not fit in u64 ticks
+        assert_eq!(TimeScale::new(1, TimeUnit::Tau).parse("1 ns"), None);
+
+        let ps10 = TimeScale::new(10, TimeUnit::Ps);
+        for ticks in [0, 1, 7, 1250, 100_001, 123_456_789] {
+            assert_eq!(ps10.parse(&ps10.format(ticks)), Some(ticks));
+        }
+    }
+}
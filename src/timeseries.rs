@@ -1,19 +1,200 @@
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub use crate::time::{TimeScale, TimeUnit};
+use crate::columns::{BitsColumn, PlainColumn, StringColumn, TimeColumn, ValueColumn};
+use crate::cache::{Cached, Reader};
+
+use std::sync::Arc;
+
+// arbitrary-width unsigned integer
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct UInt {
-    pub value: u128,
+    pub words: Vec<u64>, // little endian. bits above `width` are always zero
     pub width: usize,
 }
 
 impl UInt {
-    pub fn new(value: u128, width: usize) -> UInt {
-        UInt{value, width}
+    pub fn new(value: u64, width: usize) -> UInt {
+        let mut v = Self::zero(width);
+        v.words[0] = value;
+        v.mask();
+        v
+    }
+
+    pub fn zero(width: usize) -> UInt {
+        UInt{words: vec![0; Self::n_words(width)], width}
+    }
+
+    fn n_words(width: usize) -> usize {
+        width.div_ceil(64).max(1)
     }
+
+    // clear bits above the width
+    fn mask(&mut self) {
+        let rem = self.width % 64;
+        if rem != 0 {
+            let last = self.words.len() - 1;
+            self.words[last] &= (1_u64 << rem) - 1;
+        }
+    }
+
+    pub fn bit(&self, i: usize) -> bool {
+        (self.words[i / 64] >> (i % 64)) & 1 == 1
+    }
+
+    pub fn set_bit(&mut self, i: usize, b: bool) {
+        if b {
+            self.words[i / 64] |= 1_u64 << (i % 64);
+        } else {
+            self.words[i / 64] &= !(1_u64 << (i % 64));
+        }
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.words.iter().all(|w| *w == 0)
+    }
+
+    pub fn extend_to(&mut self, width: usize) {
+        if self.width < width {
+            self.words.resize(Self::n_words(width), 0);
+            self.width = width;
+        }
+    }
+
+    // two's complement within the width
+    fn negate(&self) -> UInt {
+        let mut v = self.clone();
+        let mut carry = true;
+        for w in v.words.iter_mut() {
+            let (x, c) = (!*w).overflowing_add(carry as u64);
+            *w = x;
+            carry = c;
+        }
+        v.mask();
+        v
+    }
+
+    // divide by `d` in place and returns the remainder
+    fn div_rem(&mut self, d: u64) -> u64 {
+        let mut rem: u128 = 0;
+        for w in self.words.iter_mut().rev() {
+            let x = (rem << 64) | (*w as u128);
+            *w = (x / d as u128) as u64;
+            rem = x % d as u128;
+        }
+        rem as u64
+    }
+
+    // each digit represents `bits_per_digit` bits. 1 for binary, 4 for hex.
+    pub fn to_digits(&self, bits_per_digit: usize) -> String {
+        Logic::new(self.clone()).to_digits(bits_per_digit)
+    }
+
+    pub fn to_decimal(&self) -> String {
+        const BASE: u64 = 10_000_000_000_000_000_000; // 10^19 < 2^64
+        let mut v = self.clone();
+        let mut chunks = Vec::new();
+        loop {
+            chunks.push(v.div_rem(BASE));
+            if v.is_zero() {
+                break;
+            }
+        }
+        let mut s = format!("{}", chunks.pop().unwrap());
+        for chunk in chunks.iter().rev() {
+            s += &format!("{:019}", chunk);
+        }
+        s
+    }
+
+    // interpret it as a two's complement number
+    pub fn to_signed_decimal(&self) -> String {
+        if self.width != 0 && self.bit(self.width - 1) {
+            format!("-{}", self.negate().to_decimal())
+        } else {
+            self.to_decimal()
+        }
+    }
+}
+
+impl std::fmt::LowerHex for UInt {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let top = self.words.iter().rposition(|w| *w != 0).unwrap_or(0);
+        let mut s = format!("{:x}", self.words[top]);
+        for w in self.words[..top].iter().rev() {
+            s += &format!("{:016x}", w);
+        }
+        f.pad_integral(true, "0x", &s)
+    }
+}
+
+// four-state vector. a bit is X if `x` is set, Z if `z` is set, otherwise `value`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Logic {
+    pub value: UInt,
+    pub x: UInt,
+    pub z: UInt,
+}
+
+impl Logic {
+    pub fn new(value: UInt) -> Self {
+        let x = UInt::zero(value.width);
+        let z = UInt::zero(value.width);
+        Logic{value, x, z}
+    }
+
+    pub fn width(&self) -> usize {
+        self.value.width
+    }
+
+    // VCD extends the leftmost bit if it is X or Z, otherwise fills zeros
+    pub fn extend_to(&mut self, width: usize) {
+        let w = self.width();
+        if width <= w {
+            return;
+        }
+        let (msb_x, msb_z) = (self.x.bit(w - 1), self.z.bit(w - 1));
+        self.value.extend_to(width);
+        self.x.extend_to(width);
+        self.z.extend_to(width);
+        for i in w..width {
+            self.x.set_bit(i, msb_x);
+            self.z.set_bit(i, msb_z);
+        }
+    }
+
+    // X takes priority over Z when a digit contains both
+    pub fn to_digits(&self, bits_per_digit: usize) -> String {
+        let width = self.width();
+        let n_digits = width.div_ceil(bits_per_digit).max(1);
+
+        let mut s = String::with_capacity(n_digits);
+        for d in (0..n_digits).rev() {
+            let lo = d * bits_per_digit;
+            let hi = (lo + bits_per_digit).min(width);
+            let (mut v, mut has_x, mut has_z) = (0, false, false);
+            for i in (lo..hi).rev() {
+                has_x |= self.x.bit(i);
+                has_z |= self.z.bit(i);
+                v = v * 2 + self.value.bit(i) as u32;
+            }
+            s.push(if has_x { 'X' } else if has_z { 'Z' } else { char::from_digit(v, 16).unwrap() });
+        }
+        let leading_zeros = s.len() - s.trim_start_matches('0').len();
+        s.split_off(leading_zeros.min(s.len() - 1))
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Radix {
+    Hex,
+    Bin,
+    Dec,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Bits {
     B(bool),
     V(UInt),
+    L(Logic), // vector that partially contains X or Z
     X,
     Z,
 }
@@ -27,9 +208,10 @@ impl Bits {
             vcd::Value::Z  => { Bits::Z },
         }
     }
+    // VCD writes a vector from the left index of the declaration, so the leftmost
+    // char is the most significant for both [7:0] and [0:7].
     pub fn from_vcd_vector(value: vcd::Vector) -> Self {
         let w = value.len();
-        assert!(w <= 128);
 
         if w == 0 {
             return Bits::B(false);
@@ -44,155 +226,709 @@ impl Bits {
         }
 
         let bits: Vec<vcd::Value> = value.iter().collect();
-        let mut v = UInt::new(0, w);
-        let mut digit: u128 = 1;
-        for bit in bits.iter().rev() {
+        Bits::from_lsb_first(bits.into_iter().rev())
+    }
+
+    // constructs a vector from bits, starting from the least significant bit
+    pub fn from_lsb_first(bits: impl ExactSizeIterator<Item = vcd::Value>) -> Self {
+        let w = bits.len();
+        let mut v = Logic::new(UInt::zero(w));
+        let (mut n_x, mut n_z) = (0, 0);
+        for (i, bit) in bits.enumerate() {
             match bit {
                 vcd::Value::V0 => { /* do nothing */ }
-                vcd::Value::V1 => { v.value += digit; }
-                vcd::Value::X  => { return Bits::X; }
-                vcd::Value::Z  => { return Bits::Z; }
+                vcd::Value::V1 => { v.value.set_bit(i, true); }
+                vcd::Value::X  => { v.x.set_bit(i, true); n_x += 1; }
+                vcd::Value::Z  => { v.z.set_bit(i, true); n_z += 1; }
+            }
+        }
+        if n_x == w {
+            Bits::X
+        } else if n_z == w {
+            Bits::Z
+        } else if n_x == 0 && n_z == 0 {
+            Bits::V(v.value)
+        } else {
+            Bits::L(v)
+        }
+    }
+
+    // the least significant bit
+    pub fn lsb(&self) -> vcd::Value {
+        match self {
+            Bits::B(x) => { if *x { vcd::Value::V1 } else { vcd::Value::V0 } }
+            Bits::V(x) => { if x.bit(0) { vcd::Value::V1 } else { vcd::Value::V0 } }
+            Bits::L(x) => {
+                if x.x.bit(0) {
+                    vcd::Value::X
+                } else if x.z.bit(0) {
+                    vcd::Value::Z
+                } else if x.value.bit(0) {
+                    vcd::Value::V1
+                } else {
+                    vcd::Value::V0
+                }
+            }
+            Bits::X => { vcd::Value::X }
+            Bits::Z => { vcd::Value::Z }
+        }
+    }
+
+    // extend a value to the declared width
+    pub fn extend_to(self, width: usize) -> Self {
+        match self {
+            Bits::B(x) if 1 < width => { Bits::V(UInt::new(x as u64, width)) }
+            Bits::V(mut x) => { x.extend_to(width); Bits::V(x) }
+            Bits::L(mut x) => { x.extend_to(width); Bits::L(x) }
+            _ => { self }
+        }
+    }
+
+    // binary shows all the bits so that each digit corresponds to a bit index
+    pub fn to_radix_string(&self, radix: Radix) -> String {
+        match self {
+            Bits::B(x) => { if *x { "1".to_string() } else { "0".to_string() } }
+            Bits::V(x) => {
+                match radix {
+                    Radix::Hex => { format!("{:x}", x) }
+                    Radix::Bin => { format!("{:0>width$}", x.to_digits(1), width = x.width) }
+                    Radix::Dec => { x.to_decimal() }
+                }
             }
-            digit <<= 1;
+            Bits::L(x) => {
+                match radix {
+                    Radix::Hex => { x.to_digits(4) }
+                    Radix::Bin => { format!("{:0>width$}", x.to_digits(1), width = x.width()) }
+                    Radix::Dec => { "X".to_string() }
+                }
+            }
+            Bits::X => { "X".to_string() }
+            Bits::Z => { "Z".to_string() }
         }
-        Bits::V(v)
+    }
+    // FST and GHW use 9-state logic (0, 1, x, z, h, u, w, l, -). fold them into 4 states.
+    pub fn from_ascii(value: &[u8]) -> Self {
+        let v: vcd::Vector = value.iter().map(|b| {
+            match b {
+                b'0' | b'l' | b'L' => { vcd::Value::V0 }
+                b'1' | b'h' | b'H' => { vcd::Value::V1 }
+                b'z' | b'Z'        => { vcd::Value::Z  }
+                _                  => { vcd::Value::X  }
+            }
+        }).collect();
+        Self::from_vcd_vector(v)
     }
 }
 
+// a value in a stream. `is_unknown` is true for X, Z, contention, ... (shown in red)
+pub trait StreamValue: std::fmt::Debug + Clone + PartialEq {
+    type Column: ValueColumn<Self>;
+
+    fn is_unknown(&self) -> bool {
+        false
+    }
+}
+
+impl StreamValue for Bits {
+    type Column = BitsColumn;
+    fn is_unknown(&self) -> bool {
+        matches!(self, Bits::X | Bits::Z | Bits::L(_))
+    }
+}
+impl StreamValue for PortValue {
+    type Column = PlainColumn<PortValue>;
+    fn is_unknown(&self) -> bool {
+        self.driver() == PortDriver::Conflict || self.bits.iter().any(|b| b.level == vcd::Value::X)
+    }
+}
+impl StreamValue for f64 {
+    type Column = PlainColumn<f64>;
+}
+impl StreamValue for String {
+    type Column = StringColumn;
+}
+impl StreamValue for () {
+    type Column = PlainColumn<()>; // takes no memory
+}
+
 #[derive(Debug, Clone, PartialEq)]
-pub struct ValueChange<T: std::fmt::Debug + Clone + PartialEq> {
+pub struct ValueChange<T: StreamValue> {
     pub time: u64,
     pub new_value: T,
 }
-impl<T: std::fmt::Debug + Clone + PartialEq> ValueChange<T> {
+impl<T: StreamValue> ValueChange<T> {
     pub fn new(time: u64, new_value: T) -> Self {
         Self{ time, new_value }
     }
 }
 
-#[derive(Debug, Clone, PartialEq)]
-pub struct ValueChangeStreamImpl<T: std::fmt::Debug + Clone + PartialEq> {
-    pub stream: Vec<ValueChange<T>>
+// whether an unknown value appears in a block of changes, for blocks of 64, 128, 256, ...
+// changes. a range of changes is checked in O(64 + log n) instead of visiting all of them,
+// so that drawing a zoomed-out trace depends on the screen width, not on the trace length.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct SummaryIndex {
+    levels: Vec<Vec<bool>>, // levels[k][j]: changes [j << (6+k), (j+1) << (6+k))
 }
 
-impl<T: std::fmt::Debug + Clone + PartialEq> ValueChangeStreamImpl<T> {
+impl SummaryIndex {
+    const LEAF: usize = 64;
 
-    pub fn new() -> Self {
-        Self{ stream: Vec::new() }
-    }
+    // call it after the change at `pos` is pushed or replaced
+    fn update<T: StreamValue>(&mut self, values: &T::Column, pos: usize, replaced: bool) {
+        let mut level = 0;
+        loop {
+            let size = Self::LEAF << level;
+            let j = pos / size;
+            if self.levels.len() == level {
+                self.levels.push(Vec::new());
+            }
+            let unknown = if 0 < level {
+                let below = &self.levels[level - 1];
+                below[2 * j] || below.get(2 * j + 1).copied().unwrap_or(false)
+            } else if replaced || pos.is_multiple_of(size) {
+                values.iter_from(j * size).take(size).any(|v| v.is_unknown())
+            } else {
+                self.levels[0][j] || values.last().is_some_and(|v| v.is_unknown())
+            };
 
-    pub fn change_before(&self, t: u64) -> Option<usize> {
-        if let Some(first) = self.stream.first() {
-            if t < first.time {
-                return None;
+            let blocks = &mut self.levels[level];
+            if j < blocks.len() {
+                if blocks[j] == unknown {
+                    break; // the blocks above do not change either
+                }
+                blocks[j] = unknown;
+            } else {
+                blocks.push(unknown);
             }
-        } else {
-            return None; // empty!
+            if values.len() <= size {
+                break; // the top
+            }
+            level += 1;
         }
+    }
+
+    // whether the blocks cover `len` changes as `update` leaves them (for a cache file)
+    fn is_consistent(&self, len: usize) -> bool {
+        let n_levels = if len == 0 { 0 } else { (0..).find(|k| len <= Self::LEAF << k).unwrap() + 1 };
+        self.levels.len() == n_levels &&
+            self.levels.iter().enumerate().all(|(k, blocks)| blocks.len() == len.div_ceil(Self::LEAF << k))
+    }
 
-        if t == 0 {
-            return Some(0); // first.time <= t, and t is u64
+    // whether an unknown value appears in changes [from, to)
+    fn any_unknown<T: StreamValue>(&self, values: &T::Column, mut from: usize, mut to: usize) -> bool {
+        // changes outside whole blocks are checked one by one
+        let head = (to - from).min(from.next_multiple_of(Self::LEAF) - from);
+        if values.iter_from(from).take(head).any(|v| v.is_unknown()) {
+            return true;
         }
+        from += head;
+        let tail = (to - from).min(to % Self::LEAF);
+        if values.iter_from(to - tail).take(tail).any(|v| v.is_unknown()) {
+            return true;
+        }
+        to -= tail;
 
-        let mut lower = 0;
-        let mut upper = self.stream.len();
-        while 1 < upper - lower {
-            assert!(lower <= upper);
-            let mid = (upper + lower) / 2;
-            let t_mid = self.stream[mid].time;
-            if t_mid < t {
-                lower = mid;
-            } else if t < t_mid {
-                upper = mid;
-            } else {
-                lower = mid;
+        let (mut lo, mut hi) = (from / Self::LEAF, to / Self::LEAF);
+        for blocks in self.levels.iter() {
+            if hi <= lo {
                 break;
             }
+            if lo % 2 == 1 {
+                if blocks[lo] {
+                    return true;
+                }
+                lo += 1;
+            }
+            if hi % 2 == 1 {
+                hi -= 1;
+                if blocks[hi] {
+                    return true;
+                }
+            }
+            lo /= 2;
+            hi /= 2;
         }
-        Some(lower)
+        false
     }
+}
 
-    pub fn change_after(&self, t: u64) -> Option<usize> {
-        if let Some(last) = self.stream.last() {
-            if last.time < t {
-                return None;
-            }
+impl Cached for SummaryIndex {
+    fn write(&self, w: &mut dyn std::io::Write) -> std::io::Result<()> {
+        self.levels.write(w)
+    }
+    fn read(r: &mut Reader<'_>) -> anyhow::Result<Self> {
+        Ok(Self{ levels: Vec::read(r)? })
+    }
+}
+
+// changes of a value, sorted by time. stored in columns, see columns.rs
+#[derive(Debug, Clone, PartialEq)]
+pub struct ValueChangeStreamImpl<T: StreamValue> {
+    times: TimeColumn,
+    values: T::Column,
+    index: SummaryIndex,
+}
+
+impl<T: StreamValue> ValueChangeStreamImpl<T> {
+
+    pub fn new() -> Self {
+        Self{ times: TimeColumn::default(), values: T::Column::default(), index: SummaryIndex::default() }
+    }
+
+    pub fn len(&self) -> usize {
+        self.times.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn value(&self, i: usize) -> T {
+        self.values.get(i)
+    }
+
+    pub fn last(&self) -> Option<ValueChange<T>> {
+        Some(ValueChange::new(self.times.last()?, self.values.last()?))
+    }
+
+    // changes from the i-th one
+    pub fn iter_from(&self, i: usize) -> impl Iterator<Item = ValueChange<T>> + '_ {
+        self.times.iter_from(i).zip(self.values.iter_from(i)).map(|(t, v)| ValueChange::new(t, v))
+    }
+
+    // if several changes happen at the same time, the last one wins.
+    // a change before the last one is taken as at the same time (loaders reject it before)
+    pub fn push_change(&mut self, t: u64, v: T) {
+        let t = self.times.last().map_or(t, |last| t.max(last));
+        let replaced = self.times.last() == Some(t);
+        if replaced {
+            self.values.pop();
         } else {
-            return None; // empty!
+            self.times.push(t);
         }
+        self.values.push(v);
+        self.index.update::<T>(&self.values, self.len() - 1, replaced);
+    }
 
-        let mut lower = 0;
-        let mut upper = self.stream.len();
-        while 1 < upper - lower {
-            assert!(lower <= upper);
-            let mid = (upper + lower) / 2;
-            let t_mid = self.stream[mid].time;
-            if t_mid < t {
-                lower = mid;
-            } else if t < t_mid {
-                upper = mid;
-            } else { // t == t_mid
-                upper = mid+1;
-                break
-            }
+    // whether X, Z, ... appears in changes [from, to)
+    pub fn any_unknown(&self, from: usize, to: usize) -> bool {
+        self.index.any_unknown::<T>(&self.values, from, to)
+    }
+
+    // the first change at or after t
+    pub fn first_change_from(&self, t: u64) -> usize {
+        self.times.lower_bound(t)
+    }
+
+    // the last change at or before t
+    pub fn change_before(&self, t: u64) -> Option<usize> {
+        self.times.upper_bound(t).checked_sub(1)
+    }
+
+    // the first change after t
+    pub fn change_after(&self, t: u64) -> Option<usize> {
+        if self.times.last()? < t {
+            return None;
         }
-        Some(upper)
+        Some(self.times.upper_bound(t))
     }
 
     pub fn last_change_time(&self) -> u64 {
-        self.stream.iter().map(|x| x.time).max().unwrap_or(0)
+        self.times.last().unwrap_or(0)
+    }
+
+    pub fn first_change_time(&self) -> Option<u64> {
+        self.times.iter_from(0).next()
+    }
+
+    // multiplies all the times, when the tick gets finer
+    pub fn scale_time(&mut self, factor: u64) {
+        let mut xs = Self::new();
+        for c in self.iter_from(0) {
+            xs.push_change(c.time.saturating_mul(factor), c.new_value);
+        }
+        *self = xs;
+    }
+}
+
+impl<T: StreamValue> Cached for ValueChangeStreamImpl<T> where T::Column: Cached {
+    fn write(&self, w: &mut dyn std::io::Write) -> std::io::Result<()> {
+        self.times.write(w)?;
+        self.values.write(w)?;
+        self.index.write(w)
+    }
+    fn read(r: &mut Reader<'_>) -> anyhow::Result<Self> {
+        let (times, values, index) = (TimeColumn::read(r)?, T::Column::read(r)?, SummaryIndex::read(r)?);
+        if times.len() != values.len() {
+            return Err(anyhow::anyhow!("broken cache: {} times and {} values", times.len(), values.len()));
+        }
+        if !index.is_consistent(values.len()) {
+            return Err(anyhow::anyhow!("broken cache: inconsistent summary index"));
+        }
+        Ok(Self{ times, values, index })
+    }
+}
+
+// which side drives a port in extended VCD
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PortDriver {
+    Input,    // the test fixture
+    Output,   // the device under test
+    Unknown,  // the direction is not known
+    Conflict, // both sides drive it (contention)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PortBit {
+    pub driver: PortDriver,
+    pub level: vcd::Value,
+    pub multiple: bool, // two or more drivers are active on the side
+}
+
+impl PortBit {
+    fn new(driver: PortDriver, level: vcd::Value, multiple: bool) -> Self {
+        Self{ driver, level, multiple }
+    }
+
+    // state characters of IEEE 1364 18.4.3.1
+    pub fn from_evcd_char(c: char) -> Option<Self> {
+        use vcd::Value::*;
+        use PortDriver::*;
+        Some(match c {
+            'D' => { Self::new(Input,    V0, false) }
+            'U' => { Self::new(Input,    V1, false) }
+            'N' => { Self::new(Input,    X,  false) }
+            'Z' => { Self::new(Input,    Z,  false) }
+            'd' => { Self::new(Input,    V0, true ) }
+            'u' => { Self::new(Input,    V1, true ) }
+            'L' => { Self::new(Output,   V0, false) }
+            'H' => { Self::new(Output,   V1, false) }
+            'X' => { Self::new(Output,   X,  false) }
+            'T' => { Self::new(Output,   Z,  false) }
+            'l' => { Self::new(Output,   V0, true ) }
+            'h' => { Self::new(Output,   V1, true ) }
+            '0' => { Self::new(Unknown,  V0, false) }
+            '1' => { Self::new(Unknown,  V1, false) }
+            '?' => { Self::new(Unknown,  X,  false) }
+            'F' => { Self::new(Unknown,  Z,  false) }
+            'f' => { Self::new(Unknown,  Z,  false) }
+            'A' | 'a' | 'B' | 'b' | 'C' | 'c' => { Self::new(Conflict, X, false) }
+            _ => { return None; }
+        })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PortValue {
+    pub bits: Vec<PortBit>, // MSB first
+    pub strength0: u8, // 0 (highz) ..= 7 (supply)
+    pub strength1: u8,
+}
+
+impl PortValue {
+    const STRONG: u8 = 6;
+
+    pub fn driver(&self) -> PortDriver {
+        let mut drivers = self.bits.iter().map(|b| b.driver);
+        let first = drivers.next().unwrap_or(PortDriver::Unknown);
+        if self.bits.iter().any(|b| b.driver == PortDriver::Conflict) {
+            PortDriver::Conflict
+        } else if drivers.all(|d| d == first) {
+            first
+        } else {
+            PortDriver::Unknown
+        }
+    }
+
+    // driven by a strength weaker than strong (pull, weak, ...)
+    pub fn is_weak(&self) -> bool {
+        self.bits.iter().any(|b| {
+            (b.level == vcd::Value::V0 && self.strength0 < Self::STRONG) ||
+            (b.level == vcd::Value::V1 && self.strength1 < Self::STRONG)
+        })
+    }
+
+    pub fn to_bits(&self) -> Bits {
+        if self.bits.len() == 1 {
+            return Bits::from_vcd_scalar(self.bits[0].level);
+        }
+        Bits::from_lsb_first(self.bits.iter().rev().map(|b| b.level))
     }
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum ValueChangeStream {
-    Bits  (ValueChangeStreamImpl<Bits>),
-    Real  (ValueChangeStreamImpl<f64>),
-    String(ValueChangeStreamImpl<String>),
-    Unknown,
+    Bits     (ValueChangeStreamImpl<Bits>),
+    Integer  (ValueChangeStreamImpl<Bits>), // integer, time. shown as a signed number
+    Parameter(ValueChangeStreamImpl<Bits>), // shown as a constant
+    Event    (ValueChangeStreamImpl<()>),   // has no value, only when it is triggered
+    Real     (ValueChangeStreamImpl<f64>),
+    String   (ValueChangeStreamImpl<String>),
+    Enum     (ValueChangeStreamImpl<Bits>, Vec<(Bits, String)>), // (value, literal)
+    Port     (ValueChangeStreamImpl<PortValue>), // extended VCD
 }
 
 impl ValueChangeStream {
     pub fn last_change_time(&self) -> u64 {
         match self {
-            Self::Bits(xs)   => { xs.last_change_time() }
-            Self::Real(xs)   => { xs.last_change_time() }
-            Self::String(xs) => { xs.last_change_time() }
-            _ => { 0 }
+            Self::Bits(xs)      => { xs.last_change_time() }
+            Self::Integer(xs)   => { xs.last_change_time() }
+            Self::Parameter(xs) => { xs.last_change_time() }
+            Self::Event(xs)     => { xs.last_change_time() }
+            Self::Real(xs)      => { xs.last_change_time() }
+            Self::String(xs)    => { xs.last_change_time() }
+            Self::Enum(xs, _)   => { xs.last_change_time() }
+            Self::Port(xs)      => { xs.last_change_time() }
         }
     }
+
+    pub fn first_change_time(&self) -> Option<u64> {
+        match self {
+            Self::Bits(xs)      => { xs.first_change_time() }
+            Self::Integer(xs)   => { xs.first_change_time() }
+            Self::Parameter(xs) => { xs.first_change_time() }
+            Self::Event(xs)     => { xs.first_change_time() }
+            Self::Real(xs)      => { xs.first_change_time() }
+            Self::String(xs)    => { xs.first_change_time() }
+            Self::Enum(xs, _)   => { xs.first_change_time() }
+            Self::Port(xs)      => { xs.first_change_time() }
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        match self {
+            Self::Bits(xs)      => { xs.len() }
+            Self::Integer(xs)   => { xs.len() }
+            Self::Parameter(xs) => { xs.len() }
+            Self::Event(xs)     => { xs.len() }
+            Self::Real(xs)      => { xs.len() }
+            Self::String(xs)    => { xs.len() }
+            Self::Enum(xs, _)   => { xs.len() }
+            Self::Port(xs)      => { xs.len() }
+        }
+    }
+
+    pub fn scale_time(&mut self, factor: u64) {
+        match self {
+            Self::Bits(xs)      => { xs.scale_time(factor) }
+            Self::Integer(xs)   => { xs.scale_time(factor) }
+            Self::Parameter(xs) => { xs.scale_time(factor) }
+            Self::Event(xs)     => { xs.scale_time(factor) }
+            Self::Real(xs)      => { xs.scale_time(factor) }
+            Self::String(xs)    => { xs.scale_time(factor) }
+            Self::Enum(xs, _)   => { xs.scale_time(factor) }
+            Self::Port(xs)      => { xs.scale_time(factor) }
+        }
+    }
+
+    // drops all the changes
+    pub fn clear(&mut self) {
+        match self {
+            Self::Bits(xs) | Self::Integer(xs) | Self::Parameter(xs) | Self::Enum(xs, _) => {
+                *xs = ValueChangeStreamImpl::new();
+            }
+            Self::Event(xs)  => { *xs = ValueChangeStreamImpl::new(); }
+            Self::Real(xs)   => { *xs = ValueChangeStreamImpl::new(); }
+            Self::String(xs) => { *xs = ValueChangeStreamImpl::new(); }
+            Self::Port(xs)   => { *xs = ValueChangeStreamImpl::new(); }
+        }
+    }
+
+    // used to group values of the same type
+    pub fn kind_order(&self) -> usize {
+        match self {
+            Self::Bits(_)      => { 0 }
+            Self::Enum(_, _)   => { 1 }
+            Self::Integer(_)   => { 2 }
+            Self::Real(_)      => { 3 }
+            Self::String(_)    => { 4 }
+            Self::Event(_)     => { 5 }
+            Self::Parameter(_) => { 6 }
+            Self::Port(_)      => { 7 }
+        }
+    }
+
+    // in the order of kind_order
+    pub const KIND_NAMES: [&'static str; 8] = ["bits", "enum", "integer", "real", "string", "event", "parameter", "port"];
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ScopeKind {
+    Module,
+    Task,
+    Function,
+    Begin, // also used for generate blocks in Verilog
+    Fork,
+    Generate,
+    Struct,
+    Union,
+    Class,
+    Interface,
+    Package,
+    Program,
+    Other,
+}
+
+impl ScopeKind {
+    pub const ALL: [ScopeKind; 13] = [
+        ScopeKind::Module, ScopeKind::Task, ScopeKind::Function, ScopeKind::Begin, ScopeKind::Fork,
+        ScopeKind::Generate, ScopeKind::Struct, ScopeKind::Union, ScopeKind::Class,
+        ScopeKind::Interface, ScopeKind::Package, ScopeKind::Program, ScopeKind::Other,
+    ];
+
+    // as written in `$scope <kind> <name> $end`
+    pub fn from_vcd_name(name: &str) -> Self {
+        match name {
+            "module"            => { ScopeKind::Module    }
+            "task"              => { ScopeKind::Task      }
+            "function"          => { ScopeKind::Function  }
+            "begin"             => { ScopeKind::Begin     }
+            "fork"              => { ScopeKind::Fork      }
+            "generate"          => { ScopeKind::Generate  }
+            "struct"            => { ScopeKind::Struct    }
+            "union"             => { ScopeKind::Union     }
+            "class"             => { ScopeKind::Class     }
+            "interface"         => { ScopeKind::Interface }
+            "package"           => { ScopeKind::Package   }
+            "program"           => { ScopeKind::Program   }
+            "vhdl_architecture" => { ScopeKind::Module    }
+            "vhdl_procedure"    => { ScopeKind::Task      }
+            "vhdl_function"     => { ScopeKind::Function  }
+            "vhdl_record"       => { ScopeKind::Struct    }
+            "vhdl_process"      => { ScopeKind::Begin     }
+            "vhdl_block"        => { ScopeKind::Begin     }
+            "vhdl_for_generate" => { ScopeKind::Generate  }
+            "vhdl_if_generate"  => { ScopeKind::Generate  }
+            "vhdl_generate"     => { ScopeKind::Generate  }
+            "vhdl_package"      => { ScopeKind::Package   }
+            _                   => { ScopeKind::Other     }
+        }
+    }
+}
+
+// how the items in a scope are listed in the sidebar and the waveform
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SortOrder {
+    Declaration,
+    Alphabetical,
+    Width,
+    Type,
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct Scope {
     pub name: String,
+    pub kind: ScopeKind,
     pub items: Vec<ScopeItem>,
+    pub order: usize, // declaration order in the parent scope
     pub open: bool, // open in sidebar tree (UI)
 }
 
 impl Scope {
-    pub fn new(name: &str) -> Self {
-        Self{ name: name.to_string(), items: Vec::new(), open: true }
+    pub fn new(name: &str, kind: ScopeKind) -> Self {
+        Self{ name: name.to_string(), kind, items: Vec::new(), order: 0, open: true }
     }
 
     pub fn should_be_rendered(&self) -> bool {
         self.items.iter().map(|x| x.should_be_rendered()).reduce(|acc, e| acc || e).unwrap_or(false)
     }
+
+    pub fn add_value(&mut self, mut v: ScopeValue) {
+        v.order = self.items.len();
+        self.items.push(ScopeItem::Value(v));
+    }
+
+    pub fn add_scope(&mut self, mut s: Scope) {
+        s.order = self.items.len();
+        self.items.push(ScopeItem::Scope(s));
+    }
+
+    // values first, then subscopes. subscopes do not have width or type,
+    // so they are listed in declaration order unless sorted alphabetically.
+    pub fn sort_items(&mut self, order: SortOrder, values: &[ValueChangeStream]) {
+        self.items.sort_by(|lhs, rhs| {
+            match (lhs, rhs) {
+                (ScopeItem::Value(_), ScopeItem::Scope(_)) => { std::cmp::Ordering::Less }
+                (ScopeItem::Scope(_), ScopeItem::Value(_)) => { std::cmp::Ordering::Greater }
+                (ScopeItem::Scope(s1), ScopeItem::Scope(s2)) => {
+                    match order {
+                        SortOrder::Alphabetical => { s1.name.cmp(&s2.name) }
+                        _                       => { s1.order.cmp(&s2.order) }
+                    }
+                }
+                (ScopeItem::Value(v1), ScopeItem::Value(v2)) => {
+                    let by_order = v1.order.cmp(&v2.order);
+                    match order {
+                        SortOrder::Declaration  => { by_order }
+                        SortOrder::Alphabetical => { v1.name.cmp(&v2.name) }
+                        SortOrder::Width => {
+                            v2.width.cmp(&v1.width).then(by_order) // wider first
+                        }
+                        SortOrder::Type => {
+                            let k1 = values[v1.index].kind_order();
+                            let k2 = values[v2.index].kind_order();
+                            k1.cmp(&k2).then(by_order)
+                        }
+                    }
+                }
+            }
+        });
+        for item in self.items.iter_mut() {
+            if let ScopeItem::Scope(s) = item {
+                s.sort_items(order, values);
+            }
+        }
+    }
+}
+
+// bit index written after the name, like `data[7:0]` or `data[3]`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BitRange {
+    Bit(i32),
+    Range(i32, i32), // [left:right]. left is the most significant, [0:7] or [7:0]
+}
+
+impl std::fmt::Display for BitRange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BitRange::Bit(b)             => { write!(f, "[{}]", b) }
+            BitRange::Range(left, right) => { write!(f, "[{}:{}]", left, right) }
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct ScopeValue {
     pub name: String,
     pub index: usize,
+    pub width: usize, // declared width in bits
+    pub range: Option<BitRange>,
+    pub order: usize, // declaration order in the scope
+    pub bits: Vec<ScopeValue>, // bits of a regrouped bus, MSB first
     pub render: bool, // (UI)
+    pub open: bool, // show bits in sidebar tree (UI)
 }
 impl ScopeValue {
-    pub fn new(name: &str, index: usize) -> Self {
-        ScopeValue{ name: name.to_string(), index, render: true }
+    pub fn new(name: &str, index: usize, width: usize) -> Self {
+        ScopeValue{ name: name.to_string(), index, width, range: None, order: 0,
+                    bits: Vec::new(), render: true, open: false }
+    }
+
+    // name with its bit index, and the width of a vector, e.g. `data[7:0] (8)`
+    pub fn label(&self) -> String {
+        let name = match self.range {
+            Some(range) => { format!("{}{}", self.name, range) }
+            None        => { self.name.clone() }
+        };
+        if 1 < self.width {
+            format!("{} ({})", name, self.width)
+        } else {
+            name
+        }
     }
 
     pub fn should_be_rendered(&self) -> bool {
-        self.render
+        self.render || self.bits.iter().any(|b| b.should_be_rendered())
     }
 }
 
@@ -211,15 +947,235 @@ impl ScopeItem {
     }
 }
 
+#[derive(Clone, Debug, Default)]
+pub struct LoadOptions {
+    pub lenient: bool, // skip broken records and keep going, collecting warnings
+    pub signals: Vec<regex::Regex>, // load only signals whose full path matches one of them. all if empty
+    pub from: Option<String>, // time window to load, e.g. `10 us` or `120` (ticks).
+    pub to: Option<String>,   // resolved with the timescale of the file
+}
+
+impl LoadOptions {
+    // `*` matches any characters (including `.`), `?` matches one character
+    pub fn glob(pattern: &str) -> anyhow::Result<regex::Regex> {
+        let mut re = String::from("^");
+        for c in pattern.chars() {
+            match c {
+                '*' => { re.push_str(".*") }
+                '?' => { re.push('.') }
+                _   => { re.push_str(&regex::escape(&c.to_string())) }
+            }
+        }
+        re.push('$');
+        Ok(regex::Regex::new(&re)?)
+    }
+
+    pub fn selects(&self, path: &str) -> bool {
+        self.signals.is_empty() || self.signals.iter().any(|re| re.is_match(path))
+    }
+
+    pub fn is_filtered(&self) -> bool {
+        !self.signals.is_empty() || self.from.is_some() || self.to.is_some()
+    }
+
+    // signals and the time window are only applied while parsing VCD
+    pub fn warn_unsupported(&self, ts: &mut TimeSeries, format: &str) {
+        if self.is_filtered() {
+            ts.warnings.push(format!("--signal, --from and --to are not supported for {}. everything is loaded", format));
+        }
+    }
+
+    // [from, to] in ticks
+    pub fn time_window(&self, scale: &TimeScale) -> anyhow::Result<(u64, u64)> {
+        let resolve = |t: &Option<String>, default: u64| -> anyhow::Result<u64> {
+            match t {
+                Some(t) => { scale.parse(t).ok_or_else(|| anyhow::anyhow!("invalid time: {} (timescale is {})", t, scale)) }
+                None    => { Ok(default) }
+            }
+        };
+        let (from, to) = (resolve(&self.from, 0)?, resolve(&self.to, u64::MAX)?);
+        if to < from {
+            return Err(anyhow::anyhow!("empty time window: {} > {}", scale.format(from), scale.format(to)));
+        }
+        Ok((from, to))
+    }
+}
+
+// `$date`, `$version` and `$comment` in the header, as written by the simulator
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct FileInfo {
+    pub date: Option<String>,
+    pub version: Option<String>,
+    pub comments: Vec<String>,
+}
+
+// a vector synthesized from bit-blasted scalars, `data[0]`, `data[1]`, ...
+#[derive(Debug, Clone, PartialEq)]
+pub struct Bus {
+    pub index: usize,
+    pub bits: Vec<Option<usize>>, // stream index of each bit, LSB first. None if not dumped
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct TimeSeries {
     pub scope: Scope,
-    pub values: Vec<ValueChangeStream>,
-    pub time_scale: (u32, String), // (100, "us")
+    pub values: Arc<Vec<ValueChangeStream>>, // shared with the thread writing the cache. see `values_mut`
+    pub time_scale: TimeScale, // a tick is 100 us, ...
+    pub dump_off: Vec<(u64, u64)>, // [from, to) where values are not dumped. to is u64::MAX if not closed
+    pub warnings: Vec<String>, // problems skipped while loading (lenient mode)
+    pub buses: Vec<Bus>,
+    pub info: FileInfo,
+}
+
+// shown in the file information panel
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Stats {
+    pub scopes: usize,
+    pub signals: [usize; 8], // declared signals of each kind, by kind_order
+    pub changes: usize,
+    pub t_first: Option<u64>,
+}
+
+impl Stats {
+    fn count(&mut self, scope: &Scope, values: &[ValueChangeStream]) {
+        for item in scope.items.iter() {
+            match item {
+                ScopeItem::Scope(s) => {
+                    self.scopes += 1;
+                    self.count(s, values);
+                }
+                ScopeItem::Value(v) => { self.count_value(v, values); }
+            }
+        }
+    }
+
+    // a regrouped bus is counted as its bits, as they are declared
+    fn count_value(&mut self, value: &ScopeValue, values: &[ValueChangeStream]) {
+        if value.bits.is_empty() {
+            self.signals[values[value.index].kind_order()] += 1;
+        }
+        for bit in value.bits.iter() {
+            self.count_value(bit, values);
+        }
+    }
 }
 
 impl TimeSeries {
     pub fn new() -> Self {
-        TimeSeries { scope: Scope::new("top"), values: Vec::new(), time_scale: (1, "tau".to_string()) }
+        TimeSeries { scope: Scope::new("top", ScopeKind::Module), values: Arc::default(), time_scale: TimeScale::new(1, TimeUnit::Tau),
+                     dump_off: Vec::new(), warnings: Vec::new(), buses: Vec::new(), info: FileInfo::default() }
+    }
+
+    // the streams to append to. they are copied only if the cache is being written from them,
+    // which happens after loading is complete
+    pub fn values_mut(&mut self) -> &mut Vec<ValueChangeStream> {
+        Arc::make_mut(&mut self.values)
+    }
+
+    // moves everything onto a finer tick, `factor` of them in the current one
+    pub fn scale_time(&mut self, scale: TimeScale, factor: u64) {
+        self.values_mut().iter_mut().for_each(|v| v.scale_time(factor));
+        for (from, to) in self.dump_off.iter_mut() {
+            *from = from.saturating_mul(factor);
+            *to   = to.saturating_mul(factor);
+        }
+        self.time_scale = scale;
+    }
+
+    pub fn stats(&self) -> Stats {
+        let mut stats = Stats::default();
+        stats.count(&self.scope, &self.values);
+
+        let buses: std::collections::HashSet<usize> = self.buses.iter().map(|b| b.index).collect();
+        let dumped = self.values.iter().enumerate().filter(|(i, _)| !buses.contains(i)).map(|(_, v)| v);
+        for v in dumped {
+            stats.changes += v.len();
+            stats.t_first = match (stats.t_first, v.first_change_time()) {
+                (Some(a), Some(b)) => { Some(a.min(b)) }
+                (a, b)             => { a.or(b) }
+            };
+        }
+        stats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // X or Z here and there, in runs and alone
+    fn stream(n: usize) -> ValueChangeStreamImpl<Bits> {
+        let mut xs = ValueChangeStreamImpl::new();
+        for i in 0..n {
+            let v = if i % 97 == 5 || (300..310).contains(&i) || i % 1000 == 999 { Bits::X } else { Bits::B(i % 2 == 0) };
+            xs.push_change(i as u64, v);
+        }
+        xs
+    }
+
+    #[test]
+    fn any_unknown_matches_brute_force() {
+        let xs = stream(2000);
+        let brute = |from: usize, to: usize| xs.iter_from(from).take(to - from).any(|c| c.new_value.is_unknown());
+        for from in (0..2000).step_by(37) {
+            for to in (from..=2000).step_by(53) {
+                assert_eq!(xs.any_unknown(from, to), brute(from, to), "[{}, {})", from, to);
+            }
+        }
+        assert!(!xs.any_unknown(310, 390));
+        assert!(xs.any_unknown(0, 2000));
+    }
+
+    #[test]
+    fn any_unknown_after_replacing_the_last_change() {
+        let mut xs = stream(128);
+        xs.push_change(200, Bits::X);
+        assert!(xs.any_unknown(128, 129));
+        xs.push_change(200, Bits::B(true)); // at the same time, replaces X
+        assert!(!xs.any_unknown(128, 129));
+        assert!(!xs.any_unknown(111, 129));
+        assert!(xs.index.is_consistent(xs.len()));
+    }
+
+    #[test]
+    fn labels() {
+        let mut v = ScopeValue::new("data", 0, 8);
+        assert_eq!(v.label(), "data (8)");
+        v.range = Some(BitRange::Range(7, 0));
+        assert_eq!(v.label(), "data[7:0] (8)");
+        let mut b = ScopeValue::new("data", 0, 1);
+        assert_eq!(b.label(), "data");
+        b.range = Some(BitRange::Bit(3));
+        assert_eq!(b.label(), "data[3]");
+    }
+
+    fn uint(words: &[u64], width: usize) -> UInt {
+        let mut v = UInt::zero(width);
+        v.words.copy_from_slice(words);
+        v.mask();
+        v
+    }
+
+    #[test]
+    fn uint_decimal() {
+        assert_eq!(UInt::zero(300).to_decimal(), "0");
+        assert_eq!(UInt::new(u64::MAX, 64).to_decimal(), "18446744073709551615");
+        assert_eq!(uint(&[0, 0, 1], 129).to_decimal(), "340282366920938463463374607431768211456");
+        assert_eq!(uint(&[u64::MAX; 4], 200).to_decimal(), "1606938044258990275541962092341162602522202993782792835301375");
+        assert_eq!(UInt::new(10_000_000_000_000_000_000, 64).to_decimal(), "10000000000000000000"); // 10^19, a chunk
+        assert_eq!(UInt::new(9_999_999_999_999_999_999, 64).to_decimal(), "9999999999999999999");
+        assert_eq!(format!("{:x}", uint(&[1, 0xab], 72)), "ab0000000000000001");
+    }
+
+    #[test]
+    fn uint_signed_decimal() {
+        assert_eq!(UInt::new(0xff, 8).to_signed_decimal(), "-1");
+        assert_eq!(UInt::new(0x80, 8).to_signed_decimal(), "-128");
+        assert_eq!(UInt::new(0x7f, 8).to_signed_decimal(), "127");
+        assert_eq!(UInt::zero(8).to_signed_decimal(), "0");
+        assert_eq!(uint(&[u64::MAX; 3], 130).to_signed_decimal(), "-1");
+        assert_eq!(uint(&[0, 1 << 5], 70).to_signed_decimal(), "-590295810358705651712");
+        assert_eq!(UInt::new(5, 8).negate(), UInt::new(0xfb, 8));
+        assert_eq!(UInt::zero(100).negate(), UInt::zero(100));
     }
 }
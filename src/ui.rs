@@ -6,7 +6,7 @@ use ratatui::style::{Style, Stylize, Color};
 use ratatui::text::{Line, Span, Text};
 use ratatui::terminal::Frame;
 use ratatui::layout::{Layout, Constraint, Direction, Rect};
-use ratatui::widgets::{Block, Borders, Paragraph};
+use ratatui::widgets::{Block, Borders, Clear, Gauge, Paragraph, Wrap};
 
 pub struct StyledString {
     string: String,
@@ -17,25 +17,259 @@ impl StyledString {
     fn styled(string: String, style: Style) -> Self {
         Self {string: string.to_string(), style}
     }
-    fn to_span(&self) -> Span {
+    fn to_span(&self) -> Span<'_> {
         Span::styled(self.string.clone(), self.style)
     }
 }
 
-fn format_time_series(timeline: &ValueChangeStream, t_from: u64, t_to: u64, width: u64) -> Vec<StyledString> {
-    if let ValueChangeStream::Bits(ts) = timeline {
-        return format_time_series_bits(ts, t_from, t_to, width);
-    } else {
-        panic!("type is unknown -> {:?}", timeline);
+fn format_time_series(timeline: &ValueChangeStream, t_from: u64, t_to: u64, width: u64, ticks_per_column: u64, radix: Radix, scalar: bool)
+    -> Vec<StyledString>
+{
+    if width < 2 {
+        return format_time_series_lod(timeline, t_from, t_to, ticks_per_column, radix, scalar);
+    }
+    match timeline {
+        ValueChangeStream::Bits(ts) => {
+            format_time_series_bits(ts, t_from, t_to, width, radix)
+        }
+        ValueChangeStream::Integer(ts) => {
+            format_time_series_text(ts, t_from, t_to, width, format_signed)
+        }
+        ValueChangeStream::Parameter(ts) => {
+            format_time_series_const(ts, t_to, (width * (t_to - t_from).max(1)) as usize)
+        }
+        ValueChangeStream::Event(ts) => {
+            format_time_series_event(ts, t_from, t_to, width)
+        }
+        ValueChangeStream::Real(ts) => {
+            format_time_series_text(ts, t_from, t_to, width, |x| format!("{}", x))
+        }
+        ValueChangeStream::String(ts) => {
+            format_time_series_text(ts, t_from, t_to, width, |x| x.clone())
+        }
+        ValueChangeStream::Enum(ts, literals) => {
+            format_time_series_text(ts, t_from, t_to, width, |x| {
+                literals.iter()
+                    .find(|(v, _)| v == x)
+                    .map(|(_, name)| name.clone())
+                    .unwrap_or_else(|| format_bits(x))
+            })
+        }
+        ValueChangeStream::Port(ts) => {
+            format_time_series_port(ts, t_from, t_to, width)
+        }
+    }
+}
+
+fn format_bits(x: &Bits) -> String {
+    x.to_radix_string(Radix::Hex)
+}
+
+fn format_signed(x: &Bits) -> String {
+    match x {
+        Bits::V(x) => { x.to_signed_decimal() }
+        _          => { format_bits(x) }
+    }
+}
+
+// parameters do not change. show the value as a constant over the whole range
+fn format_time_series_const(timeline: &ValueChangeStreamImpl<Bits>, t_to: u64, w: usize) -> Vec<StyledString> {
+    let style_const = Style::new().fg(Color::Black).bg(Color::LightBlue);
+
+    let last = timeline.change_before(t_to).or(if timeline.is_empty() { None } else { Some(0) });
+    let txt = match last {
+        Some(idx) => { format_signed(&timeline.value(idx)) }
+        None      => { String::new() }
+    };
+    let txt: String = format!("{:<width$}", txt, width = w).chars().take(w).collect();
+    vec![StyledString::styled(txt, style_const)]
+}
+
+// events have no value. show an impulse where it is triggered
+fn format_time_series_event(timeline: &ValueChangeStreamImpl<()>, t_from: u64, t_to: u64, width: u64) -> Vec<StyledString> {
+    let w = (width * (t_to - t_from).max(1)) as usize;
+    let mut line = vec!['▁'; w];
+
+    let start = timeline.change_before(t_from).unwrap_or(0);
+    for change in timeline.iter_from(start).take_while(|x| x.time < t_to) {
+        if change.time < t_from {
+            continue;
+        }
+        let pos = ((change.time - t_from) * width) as usize;
+        if pos < w {
+            line[pos] = '↑';
+        }
+    }
+    let style_bit = Style::new().fg(Color::LightGreen).bg(Color::Black);
+    vec![StyledString::styled(line.into_iter().collect(), style_bit)]
+}
+
+// show values as a sequence of boxes with its text representation
+fn format_time_series_text<T, F>(timeline: &ValueChangeStreamImpl<T>, t_from: u64, t_to: u64, width: u64, to_text: F)
+    -> Vec<StyledString>
+    where T: StreamValue,
+          F: Fn(&T) -> String
+{
+    let mut current_t = t_from;
+    let mut current_v = None;
+
+    if let Some(before_start) = timeline.change_before(t_from) {
+        current_v = Some(timeline.value(before_start));
+    }
+    let change_from = timeline.change_after(t_from);
+    let change_to   = timeline.change_after(t_to  );
+
+    let mut spans = Vec::new();
+
+    let style_var = Style::new().fg(Color::Black).bg(Color::LightGreen);
+    let style_sep = Style::new().fg(Color::LightGreen).bg(Color::Black);
+
+    let make_box = |v: Option<&T>, w: usize| {
+        match v {
+            Some(v) => {
+                let txt: String = format!("{:<width$}", to_text(v), width = w).chars().take(w).collect();
+                StyledString::styled(txt, style_var)
+            }
+            None => {
+                StyledString::styled(" ".repeat(w), Style::default())
+            }
+        }
+    };
+
+    if let Some(change_from) = change_from {
+        let change_to = change_to.unwrap_or(timeline.len());
+
+        for change in timeline.iter_from(change_from).take(change_to - change_from) {
+            let dt = (change.time - current_t).max(1);
+            let w  = (width * dt - 2) as usize;
+
+            spans.push(make_box(current_v.as_ref(), w));
+            spans.push(StyledString::styled("".to_string(), style_sep));
+
+            current_v = Some(change.new_value);
+            current_t = change.time;
+        }
+    }
+
+    if current_t < t_to {
+        let dt = (t_to - current_t).max(1);
+        let w = (width * dt) as usize;
+        spans.push(make_box(current_v.as_ref(), w));
+    }
+    spans
+}
+
+// input (test fixture) and output (DUT) sides are shown in different colors.
+// weak drives are darker, contention is red.
+fn port_color(v: &PortValue) -> Color {
+    match (v.driver(), v.is_weak()) {
+        (PortDriver::Input,    false) => { Color::LightCyan }
+        (PortDriver::Input,    true ) => { Color::Cyan }
+        (PortDriver::Output,   false) => { Color::LightGreen }
+        (PortDriver::Output,   true ) => { Color::Green }
+        (PortDriver::Unknown,  false) => { Color::White }
+        (PortDriver::Unknown,  true ) => { Color::Gray }
+        (PortDriver::Conflict, _    ) => { Color::LightRed }
+    }
+}
+
+fn format_port(v: &PortValue, w: usize) -> Vec<StyledString> {
+    let style_bad = Style::new().fg(Color::Black).bg(Color::LightRed);
+    if v.driver() == PortDriver::Conflict {
+        return vec![StyledString::styled("╳".repeat(w), style_bad)];
+    }
+    let color = port_color(v);
+    if v.bits.len() != 1 {
+        let style_var = Style::new().fg(Color::Black).bg(color);
+        return format_digits(&v.to_bits(), Radix::Hex, w, style_var, style_bad);
+    }
+    let style_bit = Style::new().fg(color).bg(Color::Black);
+    match v.bits[0].level {
+        vcd::Value::V0 => { vec![StyledString::styled("▁".repeat(w), style_bit)] }
+        vcd::Value::V1 => {
+            let glyph = if v.is_weak() { "▅" } else { "▇" };
+            vec![StyledString::styled(glyph.repeat(w), style_bit)]
+        }
+        vcd::Value::Z  => { vec![StyledString::styled("─".repeat(w), style_bit)] }
+        vcd::Value::X  => {
+            vec![StyledString::styled(format!("{:<width$}", "X", width = w), style_bad)]
+        }
+    }
+}
+
+fn format_time_series_port(timeline: &ValueChangeStreamImpl<PortValue>, t_from: u64, t_to: u64, width: u64) -> Vec<StyledString> {
+    let mut current_t = t_from;
+    let mut current_v = None;
+
+    if let Some(before_start) = timeline.change_before(t_from) {
+        current_v = Some(timeline.value(before_start));
+    }
+    let change_from = timeline.change_after(t_from);
+    let change_to   = timeline.change_after(t_to  );
+
+    let mut spans = Vec::new();
+
+    let make_segment = |v: Option<&PortValue>, w: usize| {
+        match v {
+            Some(v) => { format_port(v, w) }
+            None    => { vec![StyledString::styled(" ".repeat(w), Style::default())] }
+        }
+    };
+
+    if let Some(change_from) = change_from {
+        let change_to = change_to.unwrap_or(timeline.len());
+
+        for change in timeline.iter_from(change_from).take(change_to - change_from) {
+            // the same transitions as the other streams: a scalar steps to the new level,
+            // a vector is closed and reopened
+            let is_vector = change.new_value.bits.len() != 1;
+            let dt = (change.time - current_t).max(1);
+            let w  = (width * dt - if is_vector { 2 } else { 1 }) as usize;
+
+            spans.extend(make_segment(current_v.as_ref(), w));
+            if is_vector {
+                let left = current_v.as_ref().map_or(Color::DarkGray, port_color);
+                spans.push(StyledString::styled("".to_string(), Style::new().fg(left).bg(Color::Black)));
+                spans.push(StyledString::styled("".to_string(),
+                    Style::new().fg(port_color(&change.new_value)).bg(Color::Black)));
+            } else {
+                spans.extend(format_port(&change.new_value, 1));
+            }
+
+            current_v = Some(change.new_value);
+            current_t = change.time;
+        }
+    }
+
+    if current_t < t_to {
+        let dt = (t_to - current_t).max(1);
+        let w = (width * dt) as usize;
+        spans.extend(make_segment(current_v.as_ref(), w));
     }
+    spans
+}
+
+// pad or truncate the value text to `w` chars. X and Z digits are highlighted.
+fn format_digits(x: &Bits, radix: Radix, w: usize, style_var: Style, style_bad: Style) -> Vec<StyledString> {
+    let txt: String = format!("{:<width$}", x.to_radix_string(radix), width = w).chars().take(w).collect();
+
+    let mut spans: Vec<StyledString> = Vec::new();
+    for c in txt.chars() {
+        let sty = if c == 'X' || c == 'Z' { style_bad } else { style_var };
+        match spans.last_mut() {
+            Some(last) if last.style == sty => { last.string.push(c); }
+            _ => { spans.push(StyledString::styled(c.to_string(), sty)); }
+        }
+    }
+    spans
 }
 
-fn format_time_series_bits(timeline: &ValueChangeStreamImpl<Bits>, t_from: u64, t_to: u64, width: u64) -> Vec<StyledString> {
+fn format_time_series_bits(timeline: &ValueChangeStreamImpl<Bits>, t_from: u64, t_to: u64, width: u64, radix: Radix) -> Vec<StyledString> {
     let mut current_t = t_from;
     let mut current_v = Bits::Z;
 
     if let Some(before_start) = timeline.change_before(t_from) {
-        current_v = timeline.stream[before_start].new_value.clone();
+        current_v = timeline.value(before_start);
     }
     let change_from = timeline.change_after(t_from);
     let change_to   = timeline.change_after(t_to  );
@@ -49,43 +283,31 @@ fn format_time_series_bits(timeline: &ValueChangeStreamImpl<Bits>, t_from: u64,
     let style_bad = Style::new().fg(Color::Black).bg(Color::LightRed);
 
     if let Some(change_from) = change_from {
-        let change_to = change_to.unwrap_or(timeline.stream.len());
+        let change_to = change_to.unwrap_or(timeline.len());
 
-        for i in change_from..change_to {
+        for change in timeline.iter_from(change_from).take(change_to - change_from) {
             let mut currently_bad = false; // Z or X
 
-            let change = &timeline.stream[i];
-
             let dt = (change.time - current_t).max(1);
             let w  = (width * dt - 2) as usize;
 
-            let (mut txt, sty) = match current_v {
+            match current_v {
                 Bits::B(x) => {
                     if x {
-                        ("▇".repeat(w), style_bit)
+                        spans.push(StyledString::styled("▇".repeat(w), style_bit));
                     } else {
-                        ("▁".repeat(w), style_bit)
+                        spans.push(StyledString::styled("▁".repeat(w), style_bit));
                     }
                 }
-                Bits::V(x) => {
-                    (format!("{:<width$x}", x.value, width = w), style_var)
-                }
-                Bits::X => {
-                    currently_bad = true;
-                    (format!("{:<width$}", "X", width = w), style_bad)
+                Bits::V(_) | Bits::L(_) => {
+                    spans.extend(format_digits(&current_v, radix, w, style_var, style_bad));
                 }
-                Bits::Z => {
+                Bits::X | Bits::Z => {
                     currently_bad = true;
-                    (format!("{:<width$}", "Z", width = w), style_bad)
+                    spans.extend(format_digits(&current_v, radix, w, style_bad, style_bad));
                 }
             };
 
-            assert!(txt.chars().count() >= w);
-            if txt.chars().count() > w {
-                txt = txt.chars().take(w).collect();
-            }
-            spans.push(StyledString::styled(txt, sty));
-
             // total_width += 2;
             match change.new_value {
                 Bits::B(x) => {
@@ -95,7 +317,7 @@ fn format_time_series_bits(timeline: &ValueChangeStreamImpl<Bits>, t_from: u64,
                         spans.push(StyledString::styled("▁".to_string(), style_bit));
                     }
                 }
-                Bits::V(_) => {
+                Bits::V(_) | Bits::L(_) => {
                     spans.push(StyledString::styled("".to_string(),
                         Style::new().fg(Color::LightGreen).bg(Color::Black)
                     ));
@@ -129,7 +351,7 @@ fn format_time_series_bits(timeline: &ValueChangeStreamImpl<Bits>, t_from: u64,
                     }
                 }
             };
-            current_v = change.new_value.clone();
+            current_v = change.new_value;
             current_t = change.time;
         }
     }
@@ -138,25 +360,226 @@ fn format_time_series_bits(timeline: &ValueChangeStreamImpl<Bits>, t_from: u64,
         let dt = (t_to - current_t).max(1);
         let w = (width * dt) as usize;
 
-        let span = match current_v {
+        match current_v {
             Bits::B(x) => {
                 if x {
-                    StyledString::styled("▇".repeat(w), style_bit)
+                    spans.push(StyledString::styled("▇".repeat(w), style_bit));
                 } else {
-                    StyledString::styled("▁".repeat(w), style_bit)
+                    spans.push(StyledString::styled("▁".repeat(w), style_bit));
                 }
             }
-            Bits::V(x) => {
-                StyledString::styled(format!("{:<width$x}", x.value, width=w), style_var)
-            }
-            Bits::X => {
-                StyledString::styled(format!("{:<width$}", "X", width=w), style_bad)
+            Bits::V(_) | Bits::L(_) => {
+                spans.extend(format_digits(&current_v, radix, w, style_var, style_bad));
             }
-            Bits::Z => {
-                StyledString::styled(format!("{:<width$}", "Z", width=w), style_bad)
+            Bits::X | Bits::Z => {
+                spans.extend(format_digits(&current_v, radix, w, style_bad, style_bad));
             }
         };
-        spans.push(span);
+    }
+    spans
+}
+
+// zoomed out below 2 columns per tick, a column covers `ticks_per_column` ticks and can
+// contain many changes. they are summarised per column instead of being drawn one by one.
+
+enum Cell<T> {
+    Stable(Option<T>),                     // no change in the column
+    Busy{ changes: usize, tainted: bool }, // tainted if X or Z appears in the column
+}
+
+// the cost depends on the number of columns, not on the number of changes
+fn summarize_columns<T: StreamValue>(timeline: &ValueChangeStreamImpl<T>, t_from: u64, columns: usize, ticks_per_column: u64)
+    -> Vec<Cell<T>>
+{
+    let mut cells = Vec::with_capacity(columns);
+    let mut i = timeline.first_change_from(t_from); // the first change after the column starts
+    for col in 0..columns as u64 {
+        let t_end = t_from + (col + 1) * ticks_per_column;
+        let current = if i == 0 { None } else { Some(timeline.value(i - 1)) };
+        let end = timeline.first_change_from(t_end);
+        if end == i {
+            cells.push(Cell::Stable(current));
+        } else {
+            let tainted = current.is_some_and(|v| v.is_unknown()) || timeline.any_unknown(i, end);
+            cells.push(Cell::Busy{ changes: end - i, tainted });
+        }
+        i = end;
+    }
+    cells
+}
+
+// shading by the number of changes in a column
+fn density_glyph(changes: usize) -> char {
+    match changes {
+        1       => { '│' }
+        2..=3   => { '░' }
+        4..=15  => { '▒' }
+        _       => { '▓' }
+    }
+}
+
+// a run of stable columns is drawn like a zoomed-in segment, busy columns one by one
+fn format_cells<T, S, B>(cells: &[Cell<T>], stable: S, busy: B) -> Vec<StyledString>
+    where T: PartialEq,
+          S: Fn(Option<&T>, usize) -> Vec<StyledString>,
+          B: Fn(usize, bool) -> StyledString
+{
+    let mut spans: Vec<StyledString> = Vec::new();
+    let mut i = 0;
+    while i < cells.len() {
+        match &cells[i] {
+            Cell::Stable(v) => {
+                let w = cells[i..].iter().take_while(|c| matches!(c, Cell::Stable(u) if u == v)).count();
+                spans.extend(stable(v.as_ref(), w));
+                i += w;
+            }
+            Cell::Busy{ changes, tainted } => {
+                let span = busy(*changes, *tainted);
+                match spans.last_mut() {
+                    Some(last) if last.style == span.style => { last.string += &span.string; }
+                    _ => { spans.push(span); }
+                }
+                i += 1;
+            }
+        }
+    }
+    spans
+}
+
+fn format_time_series_lod(timeline: &ValueChangeStream, t_from: u64, t_to: u64, ticks_per_column: u64, radix: Radix, scalar: bool)
+    -> Vec<StyledString>
+{
+    let columns = (t_to - t_from).max(1).div_ceil(ticks_per_column) as usize;
+    match timeline {
+        ValueChangeStream::Bits(ts) => {
+            format_time_series_bits_lod(ts, t_from, columns, ticks_per_column, radix, scalar)
+        }
+        ValueChangeStream::Integer(ts) => {
+            format_time_series_text_lod(ts, t_from, columns, ticks_per_column, format_signed)
+        }
+        ValueChangeStream::Parameter(ts) => {
+            format_time_series_const(ts, t_to, columns)
+        }
+        ValueChangeStream::Event(ts) => {
+            let style_bit = Style::new().fg(Color::LightGreen).bg(Color::Black);
+            let cells = summarize_columns(ts, t_from, columns, ticks_per_column);
+            let line = cells.iter().map(|c| if matches!(c, Cell::Busy{..}) { '↑' } else { '▁' }).collect();
+            vec![StyledString::styled(line, style_bit)]
+        }
+        ValueChangeStream::Real(ts) => {
+            format_time_series_text_lod(ts, t_from, columns, ticks_per_column, |x| format!("{}", x))
+        }
+        ValueChangeStream::String(ts) => {
+            format_time_series_text_lod(ts, t_from, columns, ticks_per_column, |x| x.clone())
+        }
+        ValueChangeStream::Enum(ts, literals) => {
+            format_time_series_text_lod(ts, t_from, columns, ticks_per_column, |x| {
+                literals.iter()
+                    .find(|(v, _)| v == x)
+                    .map(|(_, name)| name.clone())
+                    .unwrap_or_else(|| format_bits(x))
+            })
+        }
+        ValueChangeStream::Port(ts) => {
+            let cells = summarize_columns(ts, t_from, columns, ticks_per_column);
+            format_cells(&cells,
+                |v, w| match v {
+                    Some(v) => { format_port(v, w) }
+                    None    => { vec![StyledString::styled(" ".repeat(w), Style::default())] }
+                },
+                |changes, tainted| {
+                    let color = if tainted { Color::LightRed } else { Color::White };
+                    StyledString::styled(density_glyph(changes).to_string(), Style::new().fg(color).bg(Color::Black))
+                })
+        }
+    }
+}
+
+// scalars are shaded by the density of changes. vectors show a crossing for a single change,
+// and a busy glyph for more. columns where X or Z appears are red.
+fn format_time_series_bits_lod(timeline: &ValueChangeStreamImpl<Bits>, t_from: u64, columns: usize, ticks_per_column: u64, radix: Radix, scalar: bool)
+    -> Vec<StyledString>
+{
+    let style_bit = Style::new().fg(Color::LightGreen).bg(Color::Black);
+    let style_var = Style::new().fg(Color::Black).bg(Color::LightGreen);
+    let style_bad = Style::new().fg(Color::Black).bg(Color::LightRed);
+    let style_busy    = Style::new().fg(Color::LightGreen).bg(Color::Black);
+    let style_tainted = Style::new().fg(Color::LightRed).bg(Color::Black);
+
+    let cells = summarize_columns(timeline, t_from, columns, ticks_per_column);
+
+    format_cells(&cells,
+        |v, w| match v.unwrap_or(&Bits::Z) {
+            Bits::B(true)  => { vec![StyledString::styled("▇".repeat(w), style_bit)] }
+            Bits::B(false) => { vec![StyledString::styled("▁".repeat(w), style_bit)] }
+            x @ (Bits::V(_) | Bits::L(_)) => { format_digits(x, radix, w, style_var, style_bad) }
+            x @ (Bits::X | Bits::Z)       => { format_digits(x, radix, w, style_bad, style_bad) }
+        },
+        |changes, tainted| {
+            let glyph = if scalar {
+                density_glyph(changes)
+            } else if changes == 1 {
+                '╳'
+            } else {
+                '▒'
+            };
+            StyledString::styled(glyph.to_string(), if tainted { style_tainted } else { style_busy })
+        })
+}
+
+fn format_time_series_text_lod<T, F>(timeline: &ValueChangeStreamImpl<T>, t_from: u64, columns: usize, ticks_per_column: u64, to_text: F)
+    -> Vec<StyledString>
+    where T: StreamValue,
+          F: Fn(&T) -> String
+{
+    let style_var     = Style::new().fg(Color::Black).bg(Color::LightGreen);
+    let style_busy    = Style::new().fg(Color::LightGreen).bg(Color::Black);
+    let style_tainted = Style::new().fg(Color::LightRed).bg(Color::Black);
+
+    let cells = summarize_columns(timeline, t_from, columns, ticks_per_column);
+    format_cells(&cells,
+        |v, w| match v {
+            Some(v) => {
+                let txt: String = format!("{:<width$}", to_text(v), width = w).chars().take(w).collect();
+                vec![StyledString::styled(txt, style_var)]
+            }
+            None => { vec![StyledString::styled(" ".repeat(w), Style::default())] }
+        },
+        |changes, tainted| {
+            let glyph = if changes == 1 { "╳" } else { "▒" };
+            StyledString::styled(glyph.to_string(), if tainted { style_tainted } else { style_busy })
+        })
+}
+
+// overwrite the intervals where values are not dumped ($dumpoff) with a hatch
+fn hatch_not_dumped(line: Vec<StyledString>, dump_off: &[(u64, u64)], t_from: u64, width: u64, ticks_per_column: u64)
+    -> Vec<StyledString>
+{
+    let in_range: Vec<(usize, usize)> = dump_off.iter()
+        .filter(|(_, to)| t_from < *to)
+        .map(|(from, to)| {
+            let from = (from.saturating_sub(t_from) * width / ticks_per_column) as usize;
+            let to   = (to  .saturating_sub(t_from).saturating_mul(width).div_ceil(ticks_per_column)) as usize;
+            (from, to)
+        })
+        .collect();
+    if in_range.is_empty() {
+        return line;
+    }
+    let style_off = Style::new().fg(Color::DarkGray).bg(Color::Black);
+
+    let mut spans: Vec<StyledString> = Vec::new();
+    let mut pos = 0;
+    for span in line.iter() {
+        for c in span.string.chars() {
+            let hatched = in_range.iter().any(|(from, to)| *from <= pos && pos < *to);
+            let (c, sty) = if hatched { ('╱', style_off) } else { (c, span.style) };
+            match spans.last_mut() {
+                Some(last) if last.style == sty => { last.string.push(c); }
+                _ => { spans.push(StyledString::styled(c.to_string(), sty)); }
+            }
+            pos += 1;
+        }
     }
     spans
 }
@@ -170,7 +593,12 @@ pub fn format_values(app: & app::TuiWave, values: &[((String, String), usize)])
             &app.ts.values[*idx],
             app.t_from,
             app.t_to.min(app.t_last+1),
-            app.layout.timedelta_width);
+            app.layout.timedelta_width,
+            app.layout.ticks_per_column,
+            app.radix[*idx],
+            app.widths[*idx] == 1);
+        let line = hatch_not_dumped(line, &app.ts.dump_off, app.t_from,
+            app.layout.timedelta_width, app.layout.ticks_per_column);
 
         let path = StyledString::styled(path.clone(), Style::default().fg(Color::DarkGray));
         let name = StyledString::styled(name.clone(), Style::default().bold());
@@ -187,7 +615,7 @@ fn draw_waveform(app: &app::TuiWave, frame: &mut Frame, chunk: &Rect) {
     // the first line has all (including top and bottom) borders so takes 3 lines.
     let mut constraints = vec![Constraint::Length(3)];
     // other lines does not have top border. takes 2 lines.
-    constraints.extend(vec![Constraint::Length(2); lines.len()-1]);
+    constraints.extend(vec![Constraint::Length(2); lines.len().saturating_sub(1)]);
 
     let layout = Layout::default()
         .direction(Direction::Vertical)
@@ -350,6 +778,13 @@ fn draw_sidebar(app: &app::TuiWave, frame: &mut Frame, chunk: &Rect) {
         lines.push(Line::styled(s, sty));
     }
 
+    // problems skipped while loading (lenient mode) are printed after quitting
+    let warnings = if app.ts.warnings.is_empty() {
+        Line::default()
+    } else {
+        Line::styled(format!("{} warnings", app.ts.warnings.len()), Style::new().fg(Color::Yellow))
+    };
+
     frame.render_widget(
         Paragraph::new(
             Text::from(lines)
@@ -362,43 +797,141 @@ fn draw_sidebar(app: &app::TuiWave, frame: &mut Frame, chunk: &Rect) {
             } else {
                 symbols::border::PLAIN
             })
+            .title_bottom(warnings)
+            .title(Line::styled(format!("{:?} order", app.sort_order), Style::new().fg(Color::DarkGray)))
         ),
         names[0]);
+
+    if let Some(progress) = &app.loading {
+        draw_progress(progress, frame, &names[1]);
+    }
 }
 
-fn make_tick(app: &app::TuiWave, tick: &str) -> String {
-    "─".repeat((app.layout.timedelta_width-1) as usize) + tick
+// at the bottom of the sidebar while the file is loaded in the background
+fn draw_progress(progress: &crate::follow::Progress, frame: &mut Frame, chunk: &Rect) {
+    let area = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Fill(1), Constraint::Length(3)])
+        .split(*chunk)[1];
+
+    let (read, total) = (progress.read(), progress.total.max(1));
+    let mb = |x: u64| x as f64 / (1024.0 * 1024.0);
+    frame.render_widget(
+        Gauge::default()
+            .block(
+                Block::new()
+                .borders(Borders::ALL)
+                .border_style(Style::new().fg(Color::DarkGray))
+                .title(format!("loading {:.1} s", progress.elapsed().as_secs_f64()))
+                .title_bottom("Esc: cancel")
+            )
+            .gauge_style(Style::new().fg(Color::LightGreen).bg(Color::Black))
+            .ratio((read as f64 / total as f64).min(1.0))
+            .label(format!("{:.1} / {:.1} MB", mb(read), mb(total))),
+        area);
 }
 
-fn make_ruler(app: &app::TuiWave) -> (StyledString, StyledString) {
+// header strings and statistics, in a box over the waveform
+fn draw_info(app: &app::TuiWave, frame: &mut Frame, chunk: &Rect) {
+    let stats = app.ts.stats();
+    let info = &app.ts.info;
+    let label = |name: &str| Span::styled(format!("{:<14}", name), Style::new().fg(Color::DarkGray));
+    let field = |name: &str, value: String| Line::from(vec![label(name), Span::raw(value)]);
+
+    let mut lines = vec![
+        field("date",      info.date.clone().unwrap_or("-".to_string())),
+        field("version",   info.version.clone().unwrap_or("-".to_string())),
+        field("timescale", app.ts.time_scale.to_string()),
+    ];
+    for (i, comment) in info.comments.iter().enumerate() {
+        lines.push(field(if i == 0 { "comment" } else { "" }, comment.clone()));
+    }
+    lines.push(Line::raw(""));
+    if let Some(size) = app.file_size {
+        lines.push(field("file size", format!("{:.1} MB ({} bytes)", size as f64 / (1024.0 * 1024.0), size)));
+    }
+    lines.push(field("scopes", stats.scopes.to_string()));
+    let total: usize = stats.signals.iter().sum();
+    let kinds: Vec<String> = stats.signals.iter().zip(ValueChangeStream::KIND_NAMES)
+        .filter(|(n, _)| 0 < **n)
+        .map(|(n, name)| format!("{} {}", n, name))
+        .collect();
+    lines.push(field("signals", format!("{} ({})", total, kinds.join(", "))));
+    lines.push(field("value changes", stats.changes.to_string()));
+    let span = match stats.t_first {
+        Some(t) => { format!("{} .. {}", format_time(app, t), format_time(app, app.t_last)) }
+        None    => { "-".to_string() }
+    };
+    lines.push(field("time span", span));
 
-    let t_from = app.t_from as usize;
-    let t_to   = app.t_to   as usize;
-    let t_range = t_to - t_from;
-    let t_width = app.layout.timedelta_width as usize;
+    let w = (chunk.width * 3 / 4).max(chunk.width.min(40));
+    let h = (lines.len() as u16 + 2).min(chunk.height);
+    let area = Rect::new(chunk.x + (chunk.width - w) / 2, chunk.y + (chunk.height - h) / 2, w, h);
+    frame.render_widget(Clear, area);
+    frame.render_widget(
+        Paragraph::new(lines)
+            .wrap(Wrap{ trim: false })
+            .block(
+                Block::new()
+                .borders(Borders::ALL)
+                .title(format!(" {} ", app.file_name))
+                .title_bottom("i: close")
+            ),
+        area);
+}
 
-    assert!(t_width >= 2);
+// physical time, or just ticks if the trace has no unit
+fn format_time(app: &app::TuiWave, t: u64) -> String {
+    if app.ts.time_scale.unit == TimeUnit::Tau {
+        t.to_string()
+    } else {
+        app.ts.time_scale.format(t)
+    }
+}
 
-    let tick       = make_tick(app, "┬");
-    let first_tick = tick.repeat(9 - (t_from % 10)) + &make_tick(app, "╥");
-    let tick_x10   = tick.repeat(9)                 + &make_tick(app, "╥");
-    let ruler      = first_tick + &tick_x10.repeat(t_range / 10 + 1);
+fn make_ruler(app: &app::TuiWave) -> (StyledString, StyledString) {
 
-    let mut labels = {
-        let w = t_width * (10 - t_from%10);
-        let label = format!("{:>width$}", (t_from/10 + 1) * 10, width=w);
-        label[0..w].to_string()
-    };
-    for i in 1..(t_range/10 + 1) {
-        let t = t_from / 10 * 10 + ((i+1) * 10);
-        let w = t_width * 10;
-        let label = format!("{:>width$}", t, width=w);
-        labels += &label[0..w];
+    let columns = app.layout.stream_width as usize;
+    let t_width = app.layout.timedelta_width;
+    let ticks_per_column = app.layout.ticks_per_column;
+
+    // a tick mark every `step` ticks, and a label on every 10th mark. if the labels do not fit,
+    // on every 20th, 50th, 100th, ... mark instead
+    let step = if app.layout.is_zoomed_out() { 5 * ticks_per_column } else { 1 };
+    let widest = [app.t_from, app.t_to].iter().map(|t| format_time(app, *t).chars().count()).max().unwrap_or(0) as u64;
+    let label_step = (0..18).flat_map(|e| [1, 2, 5].map(|m| (m * 10_u64.pow(e)).saturating_mul(step * 10)))
+        .find(|n| widest < n.saturating_mul(t_width) / ticks_per_column)
+        .unwrap_or(step * 10);
+
+    let mut ruler  = vec!['─'; columns];
+    let mut labels = vec![' '; columns];
+    let mut label_end = 0;
+
+    let mut t = (app.t_from / step + 1) * step;
+    loop {
+        // the mark is on the last column before `t`
+        let col = ((t - app.t_from) * t_width / ticks_per_column) as usize;
+        if columns < col {
+            break;
+        }
+        if col == 0 {
+            // less than a column after t_from
+        } else if t.is_multiple_of(label_step) {
+            ruler[col - 1] = '╥';
+            let label: Vec<char> = format_time(app, t).chars().collect();
+            if label_end + label.len() <= col {
+                labels[col - label.len()..col].copy_from_slice(&label);
+                label_end = col;
+            }
+        } else {
+            ruler[col - 1] = '┬';
+        }
+        t += step;
     }
 
     (
-        StyledString::styled(labels, Style::default()),
-        StyledString::styled(ruler, Style::default())
+        StyledString::styled(labels.into_iter().collect(), Style::default()),
+        StyledString::styled(ruler .into_iter().collect(), Style::default())
     )
 }
 
@@ -420,7 +953,10 @@ fn draw_ruler(app: &app::TuiWave, frame: &mut Frame, chunk: &Rect) {
 
     frame.render_widget(
         Paragraph::new(
-            Text::from(format!("time [{} {}]", app.ts.time_scale.0, app.ts.time_scale.1))
+            Text::from(vec![
+                Line::raw(format!("time [{}]", app.ts.time_scale)),
+                Line::styled(format!("@ {}", format_time(app, app.t_from)), Style::new().fg(Color::DarkGray)),
+            ])
         ).block(
             Block::new()
             .borders(Borders::TOP | Borders::LEFT)
@@ -431,9 +967,22 @@ fn draw_ruler(app: &app::TuiWave, frame: &mut Frame, chunk: &Rect) {
 
     let (labels, ruler) = make_ruler(app);
 
+    // the goto prompt replaces the labels while it is typed. red until it can be parsed
+    let labels = match &app.command {
+        Some(command) => {
+            let style = if app.ts.time_scale.parse(command).is_some() {
+                Style::new().fg(Color::Yellow)
+            } else {
+                Style::new().fg(Color::Red)
+            };
+            Line::styled(format!("goto: {}_", command), style)
+        }
+        None => { Line::styled(labels.string, labels.style) }
+    };
+
     frame.render_widget(
         Paragraph::new(vec![
-            Line::styled(labels.string, labels.style),
+            labels,
             Line::styled(ruler.string, ruler.style),
         ]).block(
             Block::new()
@@ -467,4 +1016,7 @@ pub fn draw_ui(app: &app::TuiWave, frame: &mut Frame) {
     draw_sidebar(app, frame, &root[0]);
     draw_ruler(app, frame, &waveform[0]);
     draw_waveform(app, frame, &waveform[1]);
+    if app.show_info {
+        draw_info(app, frame, &frame.size());
+    }
 }
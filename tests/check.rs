@@ -0,0 +1,23 @@
+// the exit status of `tuiwave check`, which scripts rely on
+
+use std::process::Command;
+
+fn check(name: &str, vcd: &str) -> (Option<i32>, String) {
+    let dir = std::env::temp_dir().join(format!("tuiwave-check-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let fname = dir.join(name);
+    std::fs::write(&fname, vcd).unwrap();
+    let out = Command::new(env!("CARGO_BIN_EXE_tuiwave")).arg("check").arg(&fname).output().unwrap();
+    std::fs::remove_file(&fname).unwrap();
+    (out.status.code(), String::from_utf8(out.stdout).unwrap())
+}
+
+#[test]
+fn exit_status() {
+    let header = "$timescale 1ns $end\n$scope module top $end\n$var wire 1 ! a $end\n$upscope $end\n$enddefinitions $end\n";
+    assert_eq!(check("clean.vcd", &format!("{}#0\n0!\n#10\n1!\n", header)), (Some(0), String::new()));
+
+    let (status, out) = check("bad.vcd", &format!("{}#10\nq!\n#5\n1!\n", header));
+    assert_eq!(status, Some(1));
+    assert_eq!(out, "line 7 (byte 104): unexpected character at start of command\nline 8 (byte 109): timestamp goes back from 10 ns to 5 ns\n");
+}